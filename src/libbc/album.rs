@@ -0,0 +1,231 @@
+use std::collections::VecDeque;
+
+use anyhow::{Error, Result};
+use async_trait::async_trait;
+use log::info;
+
+use crate::libbc::bandcamp_api::{api, BandcampApi};
+use crate::libbc::cache;
+use crate::libbc::hooks::{self, Hook};
+use crate::libbc::progress_bar::{disable_spinner, enable_spinner, println};
+use crate::libbc::shared_data::SharedState;
+use crate::models::shared_data_models::Track;
+
+#[async_trait]
+pub trait Album {
+    /// queue the rest of the current release right after it, a failure is only reported
+    async fn expand_current(&self);
+    /// a stale track with the stream url of its item page, as it is otherwise
+    async fn refresh(&self, track: Track) -> Track;
+}
+
+#[async_trait]
+impl Album for SharedState {
+    async fn expand_current(&self) {
+        let current = self.get_current_track_info().to_track();
+        if current.results.release_url().is_none() {
+            println("album: no release page for this track");
+            return;
+        }
+
+        enable_spinner();
+        let tracks = album_tracks(api(), &current).await;
+        disable_spinner();
+
+        let tracks = match tracks {
+            Ok(a) => a,
+            Err(e) => {
+                hooks::run(Hook::Error, &current, Some(&format!("album: {}", e)));
+                println(&format!("album: {}", e));
+                return;
+            }
+        };
+        let rest = match tracks.iter().position(|x| is_same(x, &current)) {
+            Some(i) => tracks[i + 1..].to_vec(),
+            None => tracks,
+        };
+        println(&format!("album: {} tracks queued", rest.len()));
+        for t in rest.into_iter().rev() {
+            self.push_front_tracklist(t);
+        }
+    }
+
+    async fn refresh(&self, track: Track) -> Track {
+        // the cache is keyed by track id, an old url still finds it
+        if !self.take_stale(&track.url) || cache::contains(&track.url) {
+            return track;
+        }
+        let Some(url) = track.results.item_url() else {
+            return track;
+        };
+        let fresh = match api().item_tracks(vec![url]).await {
+            Ok(tracks) => tracks.into_iter().find(|x| is_same(x, &track)),
+            Err(e) => {
+                info!("refresh {} {}\r", track.url, e);
+                None
+            }
+        };
+        match fresh {
+            Some(fresh) => {
+                self.set_track_url(&track.url, &fresh.url);
+                Track {
+                    url: fresh.url,
+                    ..track
+                }
+            }
+            None => track,
+        }
+    }
+}
+
+/// every playable track of the item's release, in album order
+async fn album_tracks(api: &dyn BandcampApi, item: &Track) -> Result<Vec<Track>> {
+    let url = item
+        .results
+        .release_url()
+        .ok_or(Error::msg("no release page"))?;
+    Ok(with_item(item, api.item_tracks(vec![url]).await?))
+}
+
+/// album pages carry no discover metadata, keep the item's
+fn with_item(item: &Track, tracks: Vec<Track>) -> Vec<Track> {
+    tracks
+        .into_iter()
+        .map(|t| Track {
+            art_id: t.art_id.or(item.art_id),
+            results: item.results.clone(),
+            genre: item.genre.clone(),
+            subgenre: item.subgenre.clone(),
+            ..t
+        })
+        .collect()
+}
+
+/// stream urls differ per request, compare track ids
+fn is_same(a: &Track, b: &Track) -> bool {
+    cache::key(&a.url) == cache::key(&b.url)
+        || (a.track == b.track && a.album_title == b.album_title)
+}
+
+/// album mode: replace each featured track with its whole release,
+/// the featured track stays when the release page can't be read
+pub async fn expand(items: VecDeque<Track>) -> VecDeque<Track> {
+    let urls = items
+        .iter()
+        .filter_map(|x| x.results.release_url())
+        .collect::<Vec<_>>();
+    let mut pages = api().resolve(urls).await.unwrap_or_default();
+
+    let mut v = VecDeque::new();
+    for item in items {
+        let page = item.results.release_url().and_then(|url| {
+            let i = pages.iter().position(|x| x.0 == url)?;
+            pages.remove(i).1.ok()
+        });
+        match page {
+            Some(tracks) if !tracks.is_empty() => v.extend(with_item(&item, tracks)),
+            _ => v.push_back(item),
+        }
+    }
+    v
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{Error, Result};
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use futures::stream::BoxStream;
+    use serde_json::json;
+
+    use super::{album_tracks, is_same};
+    use crate::libbc::bandcamp_api::BandcampApi;
+    use crate::models::bc_discover_index::{DiscoverIndexRequest, PostData};
+    use crate::models::bc_discover_json::DiscoverJsonRequest;
+    use crate::models::bc_discover_tags::{DiscoverTagsJson, TagsPostData};
+    use crate::models::search_models::{SearchJsonRequest, SearchJsonResponse};
+    use crate::models::shared_data_models::{ResultsJson, Track};
+
+    const ALBUM: &str = "https://a.bandcamp.com/album/b";
+
+    /// only knows the album page
+    struct Stub;
+
+    #[async_trait]
+    impl BandcampApi for Stub {
+        fn discover_index(&self) -> Result<DiscoverIndexRequest> {
+            Err(Error::msg("stub"))
+        }
+        async fn discover_web(&self, _: &PostData) -> Result<DiscoverJsonRequest> {
+            Err(Error::msg("stub"))
+        }
+        async fn related_tags(&self, _: &TagsPostData) -> Result<DiscoverTagsJson> {
+            Err(Error::msg("stub"))
+        }
+        async fn autocomplete(&self, _: &SearchJsonRequest) -> Result<SearchJsonResponse> {
+            Err(Error::msg("stub"))
+        }
+        async fn item_tracks(&self, urls: Vec<String>) -> Result<Vec<Track>> {
+            match urls.as_slice() {
+                [url] if url == ALBUM => Ok(["one", "two", "three"]
+                    .iter()
+                    .map(|x| Track {
+                        track: x.to_string(),
+                        album_title: "b".to_string(),
+                        ..Default::default()
+                    })
+                    .collect()),
+                _ => Err(Error::msg("not found")),
+            }
+        }
+        async fn resolve(&self, _: Vec<String>) -> Result<Vec<(String, Result<Vec<Track>>)>> {
+            Err(Error::msg("stub"))
+        }
+        async fn stream(&self, _: &str) -> Result<Vec<u8>> {
+            Err(Error::msg("stub"))
+        }
+        async fn stream_chunks(&self, _: &str) -> Result<BoxStream<'static, Result<Bytes>>> {
+            Err(Error::msg("stub"))
+        }
+        fn art(&self, _: i64) -> Result<Vec<u8>> {
+            Err(Error::msg("stub"))
+        }
+    }
+
+    #[test]
+    fn test_album_tracks() {
+        // a track found by search, its page is not the album page
+        let page = json!({
+            "current": {"title": "two", "art_id": 1, "band_id": 2, "release_date": ""},
+            "artist": "a",
+            "trackinfo": [],
+            "album_url": ALBUM,
+            "url": "https://a.bandcamp.com/track/two",
+        });
+        let item = Track {
+            track: "two".to_string(),
+            album_title: "b".to_string(),
+            results: ResultsJson::Search(Box::new(serde_json::from_value(page).unwrap())),
+            ..Default::default()
+        };
+        let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let tracks = rt.block_on(album_tracks(&Stub, &item)).unwrap();
+        assert_eq!(tracks.len(), 3);
+        assert!(tracks.iter().all(|x| x.results.release_url().as_deref() == Some(ALBUM)));
+    }
+
+    #[test]
+    fn test_is_same() {
+        let featured = Track {
+            url: "https://t4.bcbits.com/stream/a/mp3-128/42?p=0&ts=1&t=x".to_string(),
+            track: "b".to_string(),
+            ..Default::default()
+        };
+        let page = Track {
+            url: "https://t4.bcbits.com/stream/b/mp3-128/42?p=0&ts=2&t=y".to_string(),
+            ..Default::default()
+        };
+        assert!(is_same(&featured, &page));
+        assert!(!is_same(&featured, &Track::default()));
+    }
+}
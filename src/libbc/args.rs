@@ -1,63 +1,150 @@
-use clap::{Parser};
+use clap::{Parser, Subcommand};
 use std::fmt::Debug;
+use std::path::PathBuf;
+use std::process;
 use std::sync::Mutex;
+use std::time::Duration;
 use log::LevelFilter;
+use chrono::NaiveDate;
+use crate::libbc::config::{init_config, runtime_dir, with_config, ColorConfig, Config, FilterConfig, HooksConfig, ListenBrainzConfig};
+use crate::libbc::command::init_keymap;
+use crate::libbc::export::{ExportFormat, ExportSource};
+use crate::models::shared_data_models::Station;
 
-const ABOUT: &str = "
-A command line music player for https://bandcamp.com
-
-[Key]                [Description]
- 0-9                  adjust volume
- h                    help
- i                    play info
- s                    free word search
- f                    favorite search
- n                    play next
- m                    menu
- l                    playlist (up:k, down:j, select:enter key)
- p                    play/pause
- Q                    graceful kill
- Ctrl+C               exit";
+/// the keys are on the help screen (`h`), with the user's keybindings
+const ABOUT: &str = "A command line music player for https://bandcamp.com, press h while playing for the keys";
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about = ABOUT)]
 pub struct Args {
+    #[command(subcommand)]
+    command: Option<SubCommand>,
     /// verbose log
     #[arg(short, long, action = clap::ArgAction::Count)]
-    verbose: u8,
+    pub(crate) verbose: u8,
     /// disable SSL verification
     #[arg(long, short)]
-    no_ssl_verify: bool,
-    /// image size
-    #[arg(long, short, default_value_t = 30)]
-    img_width: u16,
+    pub(crate) no_ssl_verify: bool,
+    /// image size [default: 30]
+    #[arg(long, short)]
+    pub(crate) img_width: Option<u16>,
     /// socks5
     #[arg(hide = true, short, long, help = "socks5")]
-    proxy: Option<String>,
+    pub(crate) proxy: Option<String>,
     /// genre
     #[arg(hide = true, short, long, help = "genre")]
-    genre: Option<String>,
+    pub(crate) genre: Option<String>,
     /// sub genre
     #[arg(hide = true, short, long, help = "sub genre")]
-    sub_genre: Option<String>,
+    pub(crate) sub_genre: Option<String>,
+    /// discover location, name or geoname id (e.g. berlin, tokyo)
+    #[arg(long)]
+    pub(crate) location: Option<String>,
+    /// discover sort: rand, new, top (best-selling)
+    #[arg(long)]
+    pub(crate) slice: Option<String>,
+    /// physical format: digital, vinyl, cd, cassette..
+    #[arg(long)]
+    pub(crate) category: Option<String>,
+    /// queue whole releases instead of the featured track
+    #[arg(long)]
+    pub(crate) album_mode: bool,
+    /// skip discover items priced above this amount, needs --currency
+    #[arg(long, value_name = "AMOUNT")]
+    pub(crate) max_price: Option<f64>,
+    /// only discover items priced in this currency, e.g. USD
+    #[arg(long)]
+    pub(crate) currency: Option<String>,
+    /// free and name-your-price items only
+    #[arg(long)]
+    pub(crate) free_only: bool,
+    /// released within the last N days
+    #[arg(long, value_name = "DAYS")]
+    pub(crate) released_within: Option<i64>,
+    /// released on or after this date (YYYY-MM-DD)
+    #[arg(long, value_name = "DATE")]
+    pub(crate) released_after: Option<NaiveDate>,
+    /// released on or before this date (YYYY-MM-DD)
+    #[arg(long, value_name = "DATE")]
+    pub(crate) released_before: Option<NaiveDate>,
     /// list host devices
     #[arg(hide = true, short, num_args(0), required = false)]
     list_devices: bool,
+    /// bandcamp base url (mock server or caching proxy) [default: https://bandcamp.com]
+    #[arg(long)]
+    pub(crate) base_url: Option<String>,
+    /// album art base url [default: https://f4.bcbits.com/img]
+    #[arg(long)]
+    pub(crate) art_url: Option<String>,
+    /// upcoming tracks downloaded ahead [default: 3]
+    #[arg(long, value_name = "N")]
+    pub(crate) prefetch: Option<usize>,
+    /// wait for the whole track before playing it
+    #[arg(long)]
+    pub(crate) no_streaming: bool,
+    /// crossfade seconds between tracks, 0 is gapless [default: 0]
+    #[arg(long, value_name = "SECS")]
+    pub(crate) crossfade: Option<f32>,
+    /// serve the MPD protocol on this address, e.g. 127.0.0.1:6600
+    #[arg(long, value_name = "ADDR")]
+    pub(crate) mpd: Option<String>,
+    /// config file [default: $XDG_CONFIG_HOME/bcradio/config.toml]
+    #[arg(long)]
+    pub(crate) config: Option<PathBuf>,
+    /// play audio files from a local directory
+    #[arg(long)]
+    local: Option<PathBuf>,
+    /// queue an M3U, text or JSON playlist of bandcamp urls or local files
+    #[arg(long, value_name = "FILE")]
+    import: Option<PathBuf>,
+    /// export history or favorites and exit
+    #[arg(long, value_name = "SOURCE")]
+    export: Option<ExportSource>,
+    /// export format
+    #[arg(long, value_name = "FORMAT", default_value = "m3u8")]
+    export_format: ExportFormat,
+    /// export file, `-` for stdout [default: $XDG_DATA_HOME/bcradio/exports/]
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// print the effective configuration and exit
+    #[arg(long)]
+    print_config: bool,
 }
 
-pub fn about() -> &'static str {
-    ABOUT
+#[derive(Subcommand, Debug)]
+enum SubCommand {
+    /// control a running instance: status, next, previous, pause, volume <0-9>, seek <SECS>, enqueue <URL>.., search <WORDS>..
+    Ctl {
+        method: String,
+        params: Vec<String>,
+    },
 }
 
 static ARGS: Mutex<Option<Args>> = Mutex::new(None);
 
 pub fn init_args() {
     let arg = Args::parse();
+    let config = match Config::load(&arg) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{e:#}");
+            process::exit(1);
+        }
+    };
+    if let Err(e) = init_keymap(&config.keybindings) {
+        eprintln!("keybindings: {e:#}");
+        process::exit(1);
+    }
+    if arg.print_config {
+        print!("{}", config.to_toml().unwrap_or_default());
+        process::exit(0);
+    }
+    init_config(config);
     ARGS.lock().unwrap().replace(arg);
 }
 
 pub fn args_verbose_log() -> LevelFilter {
-    match ARGS.lock().unwrap().as_ref().unwrap().verbose {
+    match with_config(|c| c.verbose) {
         1 => LevelFilter::Info,
         2 => LevelFilter::Debug,
         3 => LevelFilter::Trace,
@@ -69,15 +156,15 @@ fn test_verbose() {
     println!("{:?}", LevelFilter::Info.to_string());
 }
 pub fn args_no_ssl_verify() -> bool {
-    ARGS.lock().unwrap().as_ref().unwrap().no_ssl_verify
+    with_config(|c| c.no_ssl_verify)
 }
 
 pub fn args_socks() -> Option<String> {
-    ARGS.lock().unwrap().as_ref().unwrap().proxy.clone()
+    with_config(|c| c.proxy.clone())
 }
 
 pub fn args_img_size() -> u16 {
-    match ARGS.lock().unwrap().as_ref().unwrap().img_width {
+    match with_config(|c| c.img_width) {
         100.. => 100,
         ..=10 => 10,
         a => a,
@@ -85,14 +172,120 @@ pub fn args_img_size() -> u16 {
 }
 
 pub fn args_genre() -> Option<String> {
-    ARGS.lock().unwrap().as_ref().unwrap().genre.to_owned()
+    with_config(|c| c.station.genre.to_owned())
 }
 
 pub fn args_sub_genre() -> Option<String> {
-    ARGS.lock().unwrap().as_ref().unwrap().sub_genre.to_owned()
+    with_config(|c| c.station.sub_genre.to_owned())
+}
+
+pub fn args_location() -> Option<String> {
+    with_config(|c| c.station.location.to_owned())
+}
+
+pub fn args_slice() -> Option<String> {
+    with_config(|c| c.station.slice.to_owned())
+}
+
+pub fn args_category() -> Option<String> {
+    with_config(|c| c.station.category.to_owned())
+}
+
+pub fn args_album_mode() -> bool {
+    with_config(|c| c.station.album_mode)
+}
+
+pub fn args_filter() -> FilterConfig {
+    with_config(|c| c.filter.to_owned())
 }
 
 pub fn args_list_devices() -> bool {
     ARGS.lock().unwrap().as_ref().unwrap().list_devices
 }
 
+pub fn args_base_url() -> String {
+    with_config(|c| c.base_url.to_owned())
+}
+
+pub fn args_art_url() -> String {
+    with_config(|c| c.art_url.to_owned())
+}
+
+pub fn args_colors() -> ColorConfig {
+    with_config(|c| c.colors.to_owned())
+}
+
+pub fn args_cache_size() -> u64 {
+    with_config(|c| c.cache_size_mb)
+}
+
+pub fn args_prefetch() -> usize {
+    with_config(|c| c.prefetch)
+}
+
+pub fn args_prefetch_memory() -> u64 {
+    with_config(|c| c.prefetch_memory_mb)
+}
+
+pub fn args_streaming() -> bool {
+    with_config(|c| c.streaming)
+}
+
+pub fn args_stream_buffer() -> usize {
+    with_config(|c| c.stream_buffer_kb.max(64))
+}
+
+/// genre, then station, then `default`
+pub fn args_crossfade(station: &Station, genre: &str) -> Duration {
+    with_config(|c| {
+        let genre = genre.to_lowercase();
+        [genre.as_str(), station.name(), "default"]
+            .iter()
+            .filter(|x| !x.is_empty())
+            .find_map(|x| c.crossfade.get(*x))
+            .map(|x| Duration::from_secs_f32(x.clamp(0.0, 30.0)))
+            .unwrap_or_default()
+    })
+}
+
+pub fn args_local() -> Option<PathBuf> {
+    ARGS.lock().unwrap().as_ref().unwrap().local.to_owned()
+}
+
+pub fn args_import() -> Option<PathBuf> {
+    ARGS.lock().unwrap().as_ref().unwrap().import.to_owned()
+}
+
+pub fn args_export() -> Option<(ExportSource, ExportFormat, Option<PathBuf>)> {
+    let lock = ARGS.lock().unwrap();
+    let args = lock.as_ref().unwrap();
+    args.export
+        .map(|source| (source, args.export_format, args.output.to_owned()))
+}
+
+/// `bcradio ctl <method> [params..]`
+pub fn args_ctl() -> Option<(String, Vec<String>)> {
+    let lock = ARGS.lock().unwrap();
+    let args = lock.as_ref().unwrap();
+    args.command
+        .as_ref()
+        .map(|SubCommand::Ctl { method, params }| (method.to_owned(), params.to_owned()))
+}
+
+pub fn args_socket() -> PathBuf {
+    with_config(|c| c.socket.clone()).unwrap_or_else(|| runtime_dir().join("bcradio.sock"))
+}
+
+pub fn args_mpd() -> Option<String> {
+    with_config(|c| c.mpd.clone())
+}
+
+pub fn args_hooks() -> HooksConfig {
+    with_config(|c| c.hooks.to_owned())
+}
+
+/// None without a token
+pub fn args_listenbrainz() -> Option<ListenBrainzConfig> {
+    with_config(|c| Some(c.listenbrainz.to_owned()))
+        .filter(|x| x.token.as_ref().is_some_and(|t| !t.is_empty()))
+}
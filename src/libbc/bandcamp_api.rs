@@ -0,0 +1,149 @@
+use std::sync::OnceLock;
+
+use anyhow::{Error, Result};
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use futures::stream::BoxStream;
+use log::error;
+use scraper::Html;
+
+use crate::libbc::args::{args_art_url, args_base_url};
+use crate::libbc::http_adapter::{html_to_track, http_adapter, http_adapter_each};
+use crate::libbc::http_client::{get_blocking_request, get_request, get_stream, post_request};
+use crate::libbc::search::parse_doc;
+use crate::models::bc_discover_index::{DiscoverIndexRequest, PostData};
+use crate::models::bc_discover_json::DiscoverJsonRequest;
+use crate::models::bc_discover_tags::{DiscoverTagsJson, TagsPostData};
+use crate::models::bc_error::BcradioError;
+use crate::models::search_models::{SearchJsonRequest, SearchJsonResponse};
+use crate::models::shared_data_models::Track;
+
+/// Every request the player sends goes through this trait,
+/// so the whole app can be pointed at a mock server or a caching proxy.
+/// `api()` is the HTTP backend for `--base-url`; code that should run
+/// against another backend (tests, stubs) takes a `&dyn BandcampApi`, as `album_tracks` does.
+#[async_trait]
+pub trait BandcampApi: Send + Sync {
+    /// `/discover/` page, genre and subgenre lists
+    fn discover_index(&self) -> Result<DiscoverIndexRequest>;
+    async fn discover_web(&self, post_data: &PostData) -> Result<DiscoverJsonRequest>;
+    async fn related_tags(&self, post_data: &TagsPostData) -> Result<DiscoverTagsJson>;
+    async fn autocomplete(&self, req: &SearchJsonRequest) -> Result<SearchJsonResponse>;
+    /// track and album pages
+    async fn item_tracks(&self, urls: Vec<String>) -> Result<Vec<Track>>;
+    /// track and album pages, result per url
+    async fn resolve(&self, urls: Vec<String>) -> Result<Vec<(String, Result<Vec<Track>>)>>;
+    /// mp3 stream
+    async fn stream(&self, url: &str) -> Result<Vec<u8>>;
+    /// mp3 stream, chunks as they arrive
+    async fn stream_chunks(&self, url: &str) -> Result<BoxStream<'static, Result<Bytes>>>;
+    /// album art thumbnail
+    fn art(&self, art_id: i64) -> Result<Vec<u8>>;
+}
+
+pub struct HttpBandcampApi {
+    base_url: String,
+    art_url: String,
+}
+
+impl HttpBandcampApi {
+    pub fn new(base_url: &str, art_url: &str) -> Self {
+        HttpBandcampApi {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            art_url: art_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    fn endpoint(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+}
+
+#[async_trait]
+impl BandcampApi for HttpBandcampApi {
+    fn discover_index(&self) -> Result<DiscoverIndexRequest> {
+        let buf = get_blocking_request(&self.endpoint("/discover/"))?;
+
+        let slice = String::from_utf8(buf)?;
+        let doc = Html::parse_document(&slice);
+
+        let c = parse_doc(doc, "div[id='DiscoverApp']", "data-blob")?;
+
+        match serde_json::from_slice(&bytes_mut(c.as_bytes())) {
+            Ok(r) => Ok(r),
+            Err(e) => {
+                error!("discover index {}\r", e);
+                Err(Error::from(e))
+            }
+        }
+    }
+
+    async fn discover_web(&self, post_data: &PostData) -> Result<DiscoverJsonRequest> {
+        let url = self.endpoint("/api/discover/1/discover_web");
+        let a = post_request(&url, post_data).await?;
+        Ok(serde_json::from_slice(&bytes_mut(a.as_slice()))?)
+    }
+
+    async fn related_tags(&self, post_data: &TagsPostData) -> Result<DiscoverTagsJson> {
+        let url = self.endpoint("/api/tag_search/2/related_tags");
+        let a = post_request(&url, post_data).await?;
+        Ok(serde_json::from_slice(&bytes_mut(a.as_slice()))?)
+    }
+
+    async fn autocomplete(&self, req: &SearchJsonRequest) -> Result<SearchJsonResponse> {
+        let url = self.endpoint("/api/bcsearch_public_api/1/autocomplete_elastic");
+        let mut val = post_request(&url, req).await?;
+        match simd_json::from_slice::<SearchJsonResponse>(val.as_mut_slice()) {
+            Ok(r) => Ok(r),
+            Err(_) => Err(Error::from(BcradioError::InvalidJsonResponse)),
+        }
+    }
+
+    async fn item_tracks(&self, urls: Vec<String>) -> Result<Vec<Track>> {
+        http_adapter(urls, html_to_track).await
+    }
+
+    async fn resolve(&self, urls: Vec<String>) -> Result<Vec<(String, Result<Vec<Track>>)>> {
+        http_adapter_each(urls, html_to_track).await
+    }
+
+    async fn stream(&self, url: &str) -> Result<Vec<u8>> {
+        get_request(url).await
+    }
+
+    async fn stream_chunks(&self, url: &str) -> Result<BoxStream<'static, Result<Bytes>>> {
+        get_stream(url).await
+    }
+
+    fn art(&self, art_id: i64) -> Result<Vec<u8>> {
+        get_blocking_request(&format!("{}/a{}_16.jpg", self.art_url, art_id))
+    }
+}
+
+static API: OnceLock<Box<dyn BandcampApi>> = OnceLock::new();
+
+pub fn api() -> &'static dyn BandcampApi {
+    API.get_or_init(|| Box::new(HttpBandcampApi::new(&args_base_url(), &args_art_url())))
+        .as_ref()
+}
+
+pub(crate) fn bytes_mut(a: &[u8]) -> BytesMut {
+    let mut b = BytesMut::new();
+    b.extend_from_slice(a);
+    b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HttpBandcampApi;
+
+    #[test]
+    fn test_endpoint() {
+        let api = HttpBandcampApi::new("http://localhost:8080/", "http://localhost:8080/img/");
+        assert_eq!(
+            api.endpoint("/api/discover/1/discover_web"),
+            "http://localhost:8080/api/discover/1/discover_web"
+        );
+        assert_eq!(api.art_url, "http://localhost:8080/img");
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::sync::LazyLock;
+use std::time::SystemTime;
+
+use anyhow::{Error, Result};
+use log::{error, info};
+use rand::seq::SliceRandom;
+use regex::Regex;
+
+use crate::lazy_regex;
+use crate::libbc::args::args_cache_size;
+use crate::libbc::config::cache_dir;
+use crate::libbc::progress_bar::println;
+use crate::libbc::shared_data::SharedState;
+use crate::models::shared_data_models::Track;
+
+/// tracks appended per fallback
+const FALLBACK_SIZE: usize = 10;
+
+pub trait Cache {
+    /// keep playing from the disk cache, returns false when nothing is cached
+    fn fallback_to_cache(&self) -> bool;
+    /// drop the track that failed to download, the cache only fills in when the
+    /// queue has run out or the network is down. returns false when nothing is left to play
+    fn drop_failed(&self, url: &str, e: &Error) -> bool;
+}
+
+impl Cache for SharedState {
+    fn fallback_to_cache(&self) -> bool {
+        let queued = self
+            .get_tracklist()
+            .iter()
+            .map(|x| key(&x.url))
+            .collect::<Vec<_>>();
+        let mut tracks = cached_tracks()
+            .into_iter()
+            .filter(|x| !queued.contains(&key(&x.url)))
+            .collect::<Vec<_>>();
+        if tracks.is_empty() {
+            return !queued.is_empty();
+        }
+
+        println("offline: playing cached tracks");
+        tracks.shuffle(&mut rand::thread_rng());
+        tracks.truncate(FALLBACK_SIZE);
+        self.append_tracklist(VecDeque::from(tracks));
+        true
+    }
+
+    fn drop_failed(&self, url: &str, e: &Error) -> bool {
+        println(&format!("skip: {}", e));
+        self.remove_track(url);
+        if !is_offline(e) && self.queue_length_from_truck_list() > 0 {
+            return true;
+        }
+        self.fallback_to_cache()
+    }
+}
+
+/// the request never reached the server, as opposed to one track that can't be had
+fn is_offline(e: &Error) -> bool {
+    e.chain()
+        .filter_map(|x| x.downcast_ref::<reqwest::Error>())
+        .any(|x| x.is_connect() || x.is_timeout())
+}
+
+lazy_regex!(RE_TRACK_ID: r"/mp3-128/(\d+)");
+
+/// stream urls carry expiring tokens, so the key is the track id
+/// or a hash of the url without its query string
+pub fn key(url: &str) -> String {
+    match RE_TRACK_ID.captures(url) {
+        Some(c) => format!("t{}", &c[1]),
+        None => {
+            let url = url.split(['?', '#']).next().unwrap_or_default();
+            format!("u{:016x}", fnv1a(url.as_bytes()))
+        }
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |h, b| {
+        (h ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn audio_path(key: &str) -> PathBuf {
+    cache_dir().join(format!("{key}.mp3"))
+}
+
+fn meta_path(key: &str) -> PathBuf {
+    cache_dir().join(format!("{key}.json"))
+}
+
+pub fn enabled() -> bool {
+    args_cache_size() > 0
+}
+
+pub fn contains(url: &str) -> bool {
+    enabled() && audio_path(&key(url)).exists()
+}
+
+pub fn get(url: &str) -> Option<Vec<u8>> {
+    if !enabled() {
+        return None;
+    }
+    let path = audio_path(&key(url));
+    let buf = fs::read(&path).ok()?;
+    // mtime is the LRU clock
+    if let Ok(f) = File::options().write(true).open(&path) {
+        let _ = f.set_modified(SystemTime::now());
+    }
+    info!("cache hit {}\r", url);
+    Some(buf)
+}
+
+pub fn put(track: &Track, buf: &[u8]) {
+    if !enabled() {
+        return;
+    }
+    let key = key(&track.url);
+    let res = fs::create_dir_all(cache_dir())
+        .and_then(|_| fs::write(audio_path(&key), buf))
+        .and_then(|_| fs::write(meta_path(&key), serde_json::to_vec(track)?));
+    match res {
+        Ok(_) => {
+            if let Err(e) = evict(args_cache_size() * 1024 * 1024) {
+                error!("cache evict {}\r", e);
+            }
+        }
+        Err(e) => error!("cache put {}\r", e),
+    }
+}
+
+/// remove least recently used entries until the cache fits in `cap` bytes
+fn evict(cap: u64) -> Result<()> {
+    let mut entries = fs::read_dir(cache_dir())?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().extension().is_some_and(|x| x == "mp3"))
+        .filter_map(|e| {
+            let m = e.metadata().ok()?;
+            Some((m.modified().ok()?, m.len(), e.path()))
+        })
+        .collect::<Vec<_>>();
+
+    let mut total = entries.iter().map(|x| x.1).sum::<u64>();
+    entries.sort_by_key(|x| x.0);
+    for (_, len, path) in entries {
+        if total <= cap {
+            break;
+        }
+        fs::remove_file(&path)?;
+        let _ = fs::remove_file(path.with_extension("json"));
+        total -= len;
+    }
+    Ok(())
+}
+
+pub fn cached_tracks() -> Vec<Track> {
+    let Ok(dir) = fs::read_dir(cache_dir()) else {
+        return Vec::new();
+    };
+    dir.filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|x| x == "json"))
+        .filter(|p| p.with_extension("mp3").exists())
+        .filter_map(|p| serde_json::from_slice(&fs::read(p).ok()?).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::key;
+
+    #[test]
+    fn test_key() {
+        let a = "https://t4.bcbits.com/stream/0f1e/mp3-128/1234567?p=0&ts=1&t=ab&token=1_cd";
+        let b = "https://t1.bcbits.com/stream/0f1e/mp3-128/1234567?p=0&ts=2&t=ef&token=2_gh";
+        assert_eq!(key(a), "t1234567");
+        assert_eq!(key(a), key(b));
+        assert_eq!(key("/music/a.mp3?x=1"), key("/music/a.mp3"));
+        assert_ne!(key("/music/a.mp3"), key("/music/b.mp3"));
+    }
+}
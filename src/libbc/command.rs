@@ -0,0 +1,373 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use anyhow::{Error, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// what a key or a control request does, sent to the player
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// 0 to 9
+    Volume(u8),
+    Help,
+    Info,
+    Search,
+    ArtistSearch,
+    ToggleFavorite,
+    Favorites,
+    Next,
+    Previous,
+    /// seconds, negative is backwards
+    Seek(i32),
+    /// seconds from the start
+    SeekTo(u64),
+    Jump,
+    ExpandAlbum,
+    Shuffle,
+    Repeat,
+    StopAfter,
+    NextSlice,
+    Export,
+    Import,
+    History,
+    Menu,
+    Playlist,
+    TogglePause,
+    Quit,
+    /// bandcamp urls or local files, from the control socket
+    Enqueue(Vec<String>),
+    /// free word search, from the control socket
+    SearchFor(String),
+    /// queue position to play right away, from the MPD server
+    PlayQueued(usize),
+    /// a download failed and left nothing to play, from the prefetch worker
+    Failed(String),
+}
+
+impl Command {
+    /// full screen commands, the key reader waits until they are done
+    pub fn parks(&self) -> bool {
+        matches!(
+            self,
+            Command::Search
+                | Command::Help
+                | Command::Menu
+                | Command::Info
+                | Command::Playlist
+                | Command::History
+                | Command::Favorites
+                | Command::Export
+                | Command::Import
+                | Command::Jump
+        )
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Command::Volume(_) => "adjust volume",
+            Command::Help => "help",
+            Command::Info => "play info",
+            Command::Search => "free word search",
+            Command::ArtistSearch => "artist search",
+            Command::ToggleFavorite => "star/unstar current track",
+            Command::Favorites => "favorites (play:enter, remove:d)",
+            Command::Next => "play next",
+            Command::Previous => "restart the track, or play the previous one",
+            Command::Seek(n) if *n < 0 => return format!("seek back {} seconds", -n),
+            Command::Seek(n) => return format!("seek forward {} seconds", n),
+            Command::SeekTo(n) => return format!("seek to {} seconds", n),
+            Command::Jump => "jump to a percentage of the track",
+            Command::ExpandAlbum => "queue the rest of the current album",
+            Command::Shuffle => "toggle shuffle of the queue",
+            Command::Repeat => "cycle repeat (off/track/queue)",
+            Command::StopAfter => "toggle stop after the current track",
+            Command::NextSlice => "cycle discover sort (random/new/best-selling)",
+            Command::Export => "export queue/history/favorites",
+            Command::Import => "import playlist (m3u/text/json) into the queue",
+            Command::History => "history (up:k, down:j, page:PgUp/PgDn, re-queue:enter key)",
+            Command::Menu => "menu",
+            Command::Playlist => "playlist (up:k, down:j, select:enter key)",
+            Command::TogglePause => "play/pause",
+            Command::Quit => "graceful kill",
+            Command::Enqueue(_) => "queue urls",
+            Command::SearchFor(_) => "free word search",
+            Command::PlayQueued(_) => "play a queued track",
+            Command::Failed(_) => "report a failed download",
+        }
+        .to_string()
+    }
+}
+
+/// `next`, `volume:5`, `seek:-10`
+impl FromStr for Command {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Command> {
+        let (name, arg) = match s.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (s, None),
+        };
+        let cmd = match (name, arg) {
+            ("volume", Some(v)) => match v.parse() {
+                Ok(v @ 0..=9) => Command::Volume(v),
+                _ => return Err(Error::msg(format!("volume is 0 to 9: {}", s))),
+            },
+            ("seek", Some(v)) => Command::Seek(
+                v.parse()
+                    .map_err(|_| Error::msg(format!("seek takes seconds: {}", s)))?,
+            ),
+            ("help", None) => Command::Help,
+            ("info", None) => Command::Info,
+            ("search", None) => Command::Search,
+            ("artist-search", None) => Command::ArtistSearch,
+            ("favorite", None) => Command::ToggleFavorite,
+            ("favorites", None) => Command::Favorites,
+            ("next", None) => Command::Next,
+            ("previous", None) => Command::Previous,
+            ("jump", None) => Command::Jump,
+            ("album", None) => Command::ExpandAlbum,
+            ("shuffle", None) => Command::Shuffle,
+            ("repeat", None) => Command::Repeat,
+            ("stop-after", None) => Command::StopAfter,
+            ("next-slice", None) => Command::NextSlice,
+            ("export", None) => Command::Export,
+            ("import", None) => Command::Import,
+            ("history", None) => Command::History,
+            ("menu", None) => Command::Menu,
+            ("playlist", None) => Command::Playlist,
+            ("toggle-pause", None) => Command::TogglePause,
+            ("quit", None) => Command::Quit,
+            _ => return Err(Error::msg(format!("unknown command: {}", s))),
+        };
+        Ok(cmd)
+    }
+}
+
+/// a key with its modifiers, shift is part of the character
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Key {
+        let modifiers = match code {
+            KeyCode::Char(_) => modifiers - KeyModifiers::SHIFT,
+            _ => modifiers,
+        };
+        Key { code, modifiers }
+    }
+}
+
+impl From<&KeyEvent> for Key {
+    fn from(e: &KeyEvent) -> Key {
+        Key::new(e.code, e.modifiers)
+    }
+}
+
+const NAMED_KEYS: [(&str, KeyCode); 13] = [
+    ("space", KeyCode::Char(' ')),
+    ("enter", KeyCode::Enter),
+    ("esc", KeyCode::Esc),
+    ("tab", KeyCode::Tab),
+    ("backspace", KeyCode::Backspace),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+];
+
+/// `n`, `Q`, `ctrl+n`, `alt+left`, `f5`
+impl FromStr for Key {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Key> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+        while let Some((m, r)) = rest.split_once('+').filter(|x| !x.1.is_empty()) {
+            modifiers |= match m.to_lowercase().as_str() {
+                "ctrl" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(Error::msg(format!("unknown modifier: {}", s))),
+            };
+            rest = r;
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) if modifiers.contains(KeyModifiers::SHIFT) => {
+                KeyCode::Char(c.to_ascii_uppercase())
+            }
+            (Some(c), None) => KeyCode::Char(c),
+            _ => {
+                let name = rest.to_lowercase();
+                match NAMED_KEYS.iter().find(|x| x.0 == name) {
+                    Some((_, code)) => *code,
+                    None => match name.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                        Some(n @ 1..=12) => KeyCode::F(n),
+                        _ => return Err(Error::msg(format!("unknown key: {}", s))),
+                    },
+                }
+            }
+        };
+        Ok(Key::new(code, modifiers))
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (m, name) in [
+            (KeyModifiers::CONTROL, "ctrl+"),
+            (KeyModifiers::ALT, "alt+"),
+            (KeyModifiers::SHIFT, "shift+"),
+        ] {
+            if self.modifiers.contains(m) {
+                write!(f, "{}", name)?;
+            }
+        }
+        match (self.code, NAMED_KEYS.iter().find(|x| x.1 == self.code)) {
+            (_, Some((name, _))) => write!(f, "{}", name),
+            (KeyCode::Char(c), _) => write!(f, "{}", c),
+            (KeyCode::F(n), _) => write!(f, "f{}", n),
+            (code, _) => write!(f, "{:?}", code),
+        }
+    }
+}
+
+/// key => command, in help order
+const DEFAULT_KEYS: [(&str, &str); 38] = [
+    ("0", "volume:0"),
+    ("1", "volume:1"),
+    ("2", "volume:2"),
+    ("3", "volume:3"),
+    ("4", "volume:4"),
+    ("5", "volume:5"),
+    ("6", "volume:6"),
+    ("7", "volume:7"),
+    ("8", "volume:8"),
+    ("9", "volume:9"),
+    ("h", "help"),
+    ("i", "info"),
+    ("s", "search"),
+    ("a", "artist-search"),
+    ("f", "favorite"),
+    ("v", "favorites"),
+    ("n", "next"),
+    ("b", "previous"),
+    (",", "seek:-10"),
+    ("left", "seek:-10"),
+    (".", "seek:10"),
+    ("right", "seek:10"),
+    ("<", "seek:-60"),
+    (">", "seek:60"),
+    ("g", "jump"),
+    ("x", "album"),
+    ("z", "shuffle"),
+    ("c", "repeat"),
+    ("w", "stop-after"),
+    ("t", "next-slice"),
+    ("e", "export"),
+    ("o", "import"),
+    ("r", "history"),
+    ("m", "menu"),
+    ("l", "playlist"),
+    ("p", "toggle-pause"),
+    ("space", "toggle-pause"),
+    ("Q", "quit"),
+];
+
+pub struct Keymap(Vec<(Key, Command)>);
+
+impl Keymap {
+    /// the defaults with `bindings` on top, `none` unbinds a key
+    pub fn new(bindings: &BTreeMap<String, String>) -> Result<Keymap> {
+        let mut v = Vec::new();
+        for (key, cmd) in DEFAULT_KEYS {
+            v.push((key.parse()?, cmd.parse()?));
+        }
+        let mut keymap = Keymap(v);
+        for (key, cmd) in bindings {
+            let key = key.parse::<Key>()?;
+            keymap.0.retain(|x| x.0 != key);
+            if cmd != "none" {
+                keymap.0.push((key, cmd.parse()?));
+            }
+        }
+        Ok(keymap)
+    }
+
+    pub fn get(&self, key: Key) -> Option<Command> {
+        self.0.iter().find(|x| x.0 == key).map(|x| x.1.clone())
+    }
+
+    /// one line per description, keys that do the same thing share it
+    pub fn help(&self) -> Vec<String> {
+        let mut rows: Vec<(String, Vec<String>)> = Vec::new();
+        for (key, cmd) in &self.0 {
+            let desc = cmd.describe();
+            match rows.iter_mut().find(|x| x.0 == desc) {
+                Some(row) => row.1.push(key.to_string()),
+                None => rows.push((desc, vec![key.to_string()])),
+            }
+        }
+        rows.into_iter()
+            .map(|(desc, keys)| format!(" {:<20} {}", keys.join(" "), desc))
+            .collect()
+    }
+}
+
+static KEYMAP: OnceLock<Keymap> = OnceLock::new();
+
+pub fn init_keymap(bindings: &BTreeMap<String, String>) -> Result<()> {
+    let _ = KEYMAP.set(Keymap::new(bindings)?);
+    Ok(())
+}
+
+pub fn keymap() -> &'static Keymap {
+    KEYMAP.get_or_init(|| Keymap::new(&BTreeMap::new()).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    use super::{Command, Key, Keymap};
+
+    #[test]
+    fn test_keymap() {
+        let bindings = BTreeMap::from([
+            ("n".to_string(), "none".to_string()),
+            ("ctrl+n".to_string(), "next".to_string()),
+            ("shift+right".to_string(), "seek:30".to_string()),
+        ]);
+        let keymap = Keymap::new(&bindings).unwrap();
+        let key = |code, modifiers| Key::from(&KeyEvent::new(code, modifiers));
+
+        assert_eq!(keymap.get(key(KeyCode::Char('n'), KeyModifiers::NONE)), None);
+        assert_eq!(
+            keymap.get(key(KeyCode::Char('n'), KeyModifiers::CONTROL)),
+            Some(Command::Next)
+        );
+        assert_eq!(
+            keymap.get(key(KeyCode::Right, KeyModifiers::SHIFT)),
+            Some(Command::Seek(30))
+        );
+        // terminals report Q with shift
+        assert_eq!(
+            keymap.get(key(KeyCode::Char('Q'), KeyModifiers::SHIFT)),
+            Some(Command::Quit)
+        );
+        assert!(keymap.help().contains(&format!(" {:<20} {}", ", left", "seek back 10 seconds")));
+        assert!(Keymap::new(&BTreeMap::from([("x".to_string(), "volume:10".to_string())])).is_err());
+    }
+}
@@ -0,0 +1,435 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use anyhow::{Context, Error, Result};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::libbc::args::Args;
+
+/// Effective settings.
+/// precedence: defaults < config file < environment variables < command line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub verbose: u8,
+    pub no_ssl_verify: bool,
+    pub img_width: u16,
+    pub proxy: Option<String>,
+    pub base_url: String,
+    pub art_url: String,
+    /// audio cache size cap in MiB, 0 disables the cache
+    pub cache_size_mb: u64,
+    /// upcoming tracks downloaded ahead
+    pub prefetch: usize,
+    /// memory cap for prefetched tracks in MiB, the next track is always fetched
+    pub prefetch_memory_mb: u64,
+    /// start playing while the track is still downloading
+    pub streaming: bool,
+    /// read-ahead of a streamed track in KiB
+    pub stream_buffer_kb: usize,
+    pub station: StationConfig,
+    /// crossfade seconds by genre, station (discover, favorites, local, playlist)
+    /// or `default`, 0 is gapless
+    pub crossfade: BTreeMap<String, f32>,
+    pub filter: FilterConfig,
+    pub colors: ColorConfig,
+    /// key => command
+    pub keybindings: BTreeMap<String, String>,
+    /// control socket [default: $XDG_RUNTIME_DIR/bcradio.sock]
+    pub socket: Option<PathBuf>,
+    /// MPD protocol listen address, e.g. 127.0.0.1:6600
+    pub mpd: Option<String>,
+    pub hooks: HooksConfig,
+    pub listenbrainz: ListenBrainzConfig,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StationConfig {
+    pub genre: Option<String>,
+    pub sub_genre: Option<String>,
+    /// discover location, name or geoname id
+    pub location: Option<String>,
+    /// discover sort, e.g. rand, new, top
+    pub slice: Option<String>,
+    /// physical format, e.g. vinyl, cassette
+    pub category: Option<String>,
+    /// queue whole releases instead of the featured track
+    pub album_mode: bool,
+}
+
+/// client-side discover filters, prices are in major units
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilterConfig {
+    pub max_price: Option<f64>,
+    /// items priced in other currencies are skipped, `max_price` is in it
+    pub currency: Option<String>,
+    /// free and name-your-price only
+    pub free_only: bool,
+    pub released_within_days: Option<i64>,
+    pub released_after: Option<NaiveDate>,
+    pub released_before: Option<NaiveDate>,
+}
+
+/// shell commands run on player events, the track is in `BCRADIO_*` variables and as JSON on stdin
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HooksConfig {
+    pub on_track_start: Option<String>,
+    pub on_track_end: Option<String>,
+    pub on_pause: Option<String>,
+    pub on_resume: Option<String>,
+    pub on_error: Option<String>,
+}
+
+/// listens are submitted when a token is set
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ListenBrainzConfig {
+    /// API root of ListenBrainz or a compatible server
+    pub url: String,
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColorConfig {
+    pub time: [u8; 3],
+    pub song: [u8; 3],
+    pub artist: [u8; 3],
+    pub album: [u8; 3],
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            verbose: 0,
+            no_ssl_verify: false,
+            img_width: 30,
+            proxy: None,
+            base_url: "https://bandcamp.com".to_string(),
+            art_url: "https://f4.bcbits.com/img".to_string(),
+            cache_size_mb: 512,
+            prefetch: 3,
+            prefetch_memory_mb: 64,
+            streaming: true,
+            stream_buffer_kb: 1024,
+            station: StationConfig::default(),
+            crossfade: BTreeMap::new(),
+            filter: FilterConfig::default(),
+            colors: ColorConfig::default(),
+            keybindings: BTreeMap::new(),
+            socket: None,
+            mpd: None,
+            hooks: HooksConfig::default(),
+            listenbrainz: ListenBrainzConfig::default(),
+        }
+    }
+}
+
+impl Default for ListenBrainzConfig {
+    fn default() -> Self {
+        ListenBrainzConfig {
+            url: "https://api.listenbrainz.org".to_string(),
+            token: None,
+        }
+    }
+}
+
+impl Default for ColorConfig {
+    fn default() -> Self {
+        ColorConfig {
+            time: [90, 91, 103],
+            song: [146, 49, 176],
+            artist: [126, 87, 194],
+            album: [121, 134, 203],
+        }
+    }
+}
+
+impl Config {
+    pub fn load(args: &Args) -> Result<Config> {
+        let path = args
+            .config
+            .clone()
+            .or_else(|| env::var_os("BCRADIO_CONFIG").map(PathBuf::from))
+            .unwrap_or_else(|| config_dir().join("config.toml"));
+
+        let mut config = if path.exists() {
+            let s = fs::read_to_string(&path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            toml::from_str(&s).with_context(|| format!("invalid config {}", path.display()))?
+        } else {
+            Config::default()
+        };
+
+        config.merge_env(|k| env::var(k).ok());
+        config.merge_args(args);
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// settings that only make sense together
+    fn validate(&self) -> Result<()> {
+        if self.filter.max_price.is_some() && self.filter.currency.is_none() {
+            return Err(Error::msg("max_price needs a currency, prices differ by currency"));
+        }
+        Ok(())
+    }
+
+    fn merge_env<F>(&mut self, var: F)
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let flag = |v: String| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes" | "on");
+
+        if let Some(v) = var("BCRADIO_VERBOSE").and_then(|v| v.parse().ok()) {
+            self.verbose = v;
+        }
+        if let Some(v) = var("BCRADIO_NO_SSL_VERIFY") {
+            self.no_ssl_verify = flag(v);
+        }
+        if let Some(v) = var("BCRADIO_IMG_WIDTH").and_then(|v| v.parse().ok()) {
+            self.img_width = v;
+        }
+        if let Some(v) = var("BCRADIO_PROXY") {
+            self.proxy = Some(v);
+        }
+        if let Some(v) = var("BCRADIO_BASE_URL") {
+            self.base_url = v;
+        }
+        if let Some(v) = var("BCRADIO_ART_URL") {
+            self.art_url = v;
+        }
+        if let Some(v) = var("BCRADIO_CACHE_SIZE_MB").and_then(|v| v.parse().ok()) {
+            self.cache_size_mb = v;
+        }
+        if let Some(v) = var("BCRADIO_PREFETCH").and_then(|v| v.parse().ok()) {
+            self.prefetch = v;
+        }
+        if let Some(v) = var("BCRADIO_PREFETCH_MEMORY_MB").and_then(|v| v.parse().ok()) {
+            self.prefetch_memory_mb = v;
+        }
+        if let Some(v) = var("BCRADIO_STREAMING") {
+            self.streaming = flag(v);
+        }
+        if let Some(v) = var("BCRADIO_STREAM_BUFFER_KB").and_then(|v| v.parse().ok()) {
+            self.stream_buffer_kb = v;
+        }
+        if let Some(v) = var("BCRADIO_CROSSFADE").and_then(|v| v.parse().ok()) {
+            self.crossfade.insert("default".to_string(), v);
+        }
+        if let Some(v) = var("BCRADIO_SOCKET") {
+            self.socket = Some(PathBuf::from(v));
+        }
+        if let Some(v) = var("BCRADIO_MPD") {
+            self.mpd = Some(v);
+        }
+        if let Some(v) = var("BCRADIO_LISTENBRAINZ_URL") {
+            self.listenbrainz.url = v;
+        }
+        if let Some(v) = var("BCRADIO_LISTENBRAINZ_TOKEN") {
+            self.listenbrainz.token = Some(v);
+        }
+        if let Some(v) = var("BCRADIO_GENRE") {
+            self.station.genre = Some(v);
+        }
+        if let Some(v) = var("BCRADIO_SUB_GENRE") {
+            self.station.sub_genre = Some(v);
+        }
+        if let Some(v) = var("BCRADIO_LOCATION") {
+            self.station.location = Some(v);
+        }
+        if let Some(v) = var("BCRADIO_SLICE") {
+            self.station.slice = Some(v);
+        }
+        if let Some(v) = var("BCRADIO_CATEGORY") {
+            self.station.category = Some(v);
+        }
+        if let Some(v) = var("BCRADIO_ALBUM_MODE") {
+            self.station.album_mode = flag(v);
+        }
+        if let Some(v) = var("BCRADIO_MAX_PRICE").and_then(|v| v.parse().ok()) {
+            self.filter.max_price = Some(v);
+        }
+        if let Some(v) = var("BCRADIO_CURRENCY") {
+            self.filter.currency = Some(v);
+        }
+        if let Some(v) = var("BCRADIO_FREE_ONLY") {
+            self.filter.free_only = flag(v);
+        }
+        if let Some(v) = var("BCRADIO_RELEASED_WITHIN").and_then(|v| v.parse().ok()) {
+            self.filter.released_within_days = Some(v);
+        }
+        if let Some(v) = var("BCRADIO_RELEASED_AFTER").and_then(|v| v.parse().ok()) {
+            self.filter.released_after = Some(v);
+        }
+        if let Some(v) = var("BCRADIO_RELEASED_BEFORE").and_then(|v| v.parse().ok()) {
+            self.filter.released_before = Some(v);
+        }
+    }
+
+    fn merge_args(&mut self, args: &Args) {
+        if args.verbose > 0 {
+            self.verbose = args.verbose;
+        }
+        if args.no_ssl_verify {
+            self.no_ssl_verify = true;
+        }
+        if let Some(v) = args.img_width {
+            self.img_width = v;
+        }
+        if let Some(v) = &args.proxy {
+            self.proxy = Some(v.to_owned());
+        }
+        if let Some(v) = &args.base_url {
+            self.base_url = v.to_owned();
+        }
+        if let Some(v) = &args.art_url {
+            self.art_url = v.to_owned();
+        }
+        if let Some(v) = args.prefetch {
+            self.prefetch = v;
+        }
+        if args.no_streaming {
+            self.streaming = false;
+        }
+        if let Some(v) = args.crossfade {
+            self.crossfade.insert("default".to_string(), v);
+        }
+        if let Some(v) = &args.mpd {
+            self.mpd = Some(v.to_owned());
+        }
+        if let Some(v) = &args.genre {
+            self.station.genre = Some(v.to_owned());
+        }
+        if let Some(v) = &args.sub_genre {
+            self.station.sub_genre = Some(v.to_owned());
+        }
+        if let Some(v) = &args.location {
+            self.station.location = Some(v.to_owned());
+        }
+        if let Some(v) = &args.slice {
+            self.station.slice = Some(v.to_owned());
+        }
+        if let Some(v) = &args.category {
+            self.station.category = Some(v.to_owned());
+        }
+        if args.album_mode {
+            self.station.album_mode = true;
+        }
+        if let Some(v) = args.max_price {
+            self.filter.max_price = Some(v);
+        }
+        if let Some(v) = &args.currency {
+            self.filter.currency = Some(v.to_owned());
+        }
+        if args.free_only {
+            self.filter.free_only = true;
+        }
+        if let Some(v) = args.released_within {
+            self.filter.released_within_days = Some(v);
+        }
+        if let Some(v) = args.released_after {
+            self.filter.released_after = Some(v);
+        }
+        if let Some(v) = args.released_before {
+            self.filter.released_before = Some(v);
+        }
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string(self)?)
+    }
+}
+
+static CONFIG: Mutex<Option<Config>> = Mutex::new(None);
+
+pub fn init_config(config: Config) {
+    CONFIG.lock().unwrap().replace(config);
+}
+
+pub fn with_config<T, F>(f: F) -> T
+where
+    F: FnOnce(&Config) -> T,
+{
+    f(CONFIG.lock().unwrap().as_ref().unwrap())
+}
+
+/// $XDG_CONFIG_HOME/bcradio
+pub fn config_dir() -> PathBuf {
+    base_dir("XDG_CONFIG_HOME", ".config").join("bcradio")
+}
+
+/// $XDG_DATA_HOME/bcradio
+pub fn data_dir() -> PathBuf {
+    base_dir("XDG_DATA_HOME", ".local/share").join("bcradio")
+}
+
+/// $XDG_CACHE_HOME/bcradio/audio
+pub fn cache_dir() -> PathBuf {
+    base_dir("XDG_CACHE_HOME", ".cache").join("bcradio").join("audio")
+}
+
+/// $XDG_RUNTIME_DIR, the temp directory without it
+pub fn runtime_dir() -> PathBuf {
+    env::var_os("XDG_RUNTIME_DIR")
+        .filter(|x| !x.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(env::temp_dir)
+}
+
+fn base_dir(xdg: &str, fallback: &str) -> PathBuf {
+    if let Some(dir) = env::var_os(xdg).filter(|x| !x.is_empty()) {
+        return PathBuf::from(dir);
+    }
+    #[cfg(windows)]
+    if let Some(dir) = env::var_os("APPDATA") {
+        return PathBuf::from(dir);
+    }
+    env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(env::temp_dir)
+        .join(fallback)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+    use crate::libbc::args::Args;
+    use clap::Parser;
+
+    #[test]
+    fn test_precedence() {
+        let mut config: Config = toml::from_str(
+            r#"
+            img_width = 40
+            proxy = "socks5://file:1080"
+            [station]
+            genre = "jazz"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.base_url, "https://bandcamp.com");
+
+        config.merge_env(|k| match k {
+            "BCRADIO_IMG_WIDTH" => Some("50".to_string()),
+            "BCRADIO_GENRE" => Some("ambient".to_string()),
+            "BCRADIO_RELEASED_AFTER" => Some("2024-01-01".to_string()),
+            _ => None,
+        });
+        assert_eq!(config.img_width, 50);
+        assert_eq!(config.filter.released_after, chrono::NaiveDate::from_ymd_opt(2024, 1, 1));
+
+        config.merge_args(&Args::parse_from(["bcradio", "--img-width", "60"]));
+        assert_eq!(config.img_width, 60);
+        assert_eq!(config.proxy.as_deref(), Some("socks5://file:1080"));
+        assert_eq!(config.station.genre.as_deref(), Some("ambient"));
+        assert!(config.to_toml().is_ok());
+    }
+}
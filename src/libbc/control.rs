@@ -0,0 +1,214 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::ops::Deref;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net;
+use std::path::Path;
+
+use anyhow::{Error, Result};
+use log::{info, warn};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader as AsyncBufReader};
+use tokio::net::{UnixListener, UnixStream};
+
+use crate::libbc::args::args_socket;
+use crate::libbc::command::Command;
+use crate::libbc::mode;
+use crate::libbc::player::RXTX;
+use crate::libbc::progress_bar::get_progress_bar_position;
+use crate::libbc::shared_data::SharedState;
+use crate::models::shared_data_models::Track;
+
+/// JSON-RPC 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+/// accept JSON-RPC requests, one per line, on the control socket
+pub async fn serve(state: SharedState) {
+    let path = args_socket();
+    if net::UnixStream::connect(&path).is_ok() {
+        warn!("control: {} is in use\r", path.display());
+        return;
+    }
+    // left over by an instance that was killed
+    let _ = fs::remove_file(&path);
+    let listener = match bind(&path) {
+        Ok(a) => a,
+        Err(e) => {
+            warn!("control: {} {}\r", path.display(), e);
+            return;
+        }
+    };
+    info!("control: listening on {}\r", path.display());
+
+    while let Ok((stream, _)) = listener.accept().await {
+        tokio::spawn(session(state.clone(), stream));
+    }
+}
+
+fn bind(path: &Path) -> Result<UnixListener> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+async fn session(state: SharedState, stream: UnixStream) {
+    let (r, mut w) = stream.into_split();
+    let mut lines = AsyncBufReader::new(r).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let res = handle(&state, &line).await;
+        if w.write_all(format!("{}\n", res).as_bytes()).await.is_err() {
+            break;
+        }
+    }
+}
+
+async fn handle(state: &SharedState, line: &str) -> Value {
+    let req = match serde_json::from_str::<Value>(line) {
+        Ok(a) => a,
+        Err(e) => return error(Value::Null, PARSE_ERROR, &e.to_string()),
+    };
+    let id = req.get("id").cloned().unwrap_or(Value::Null);
+    let method = req.get("method").and_then(Value::as_str).unwrap_or_default();
+    let params = req.get("params").cloned().unwrap_or(Value::Null);
+
+    let cmd = match method {
+        "status" => return result(id, status(state)),
+        "next" => Command::Next,
+        "previous" => Command::Previous,
+        "pause" => Command::TogglePause,
+        "volume" => match param(&params, "level", 0).and_then(|x| x.parse().ok()) {
+            Some(v @ 0..=9) => Command::Volume(v),
+            _ => return error(id, INVALID_PARAMS, "level is 0 to 9"),
+        },
+        "seek" => match param(&params, "position", 0).and_then(|x| x.parse().ok()) {
+            Some(secs) => Command::SeekTo(secs),
+            None => return error(id, INVALID_PARAMS, "position is in seconds"),
+        },
+        "enqueue" => match list(&params, "url") {
+            urls if !urls.is_empty() => Command::Enqueue(urls),
+            _ => return error(id, INVALID_PARAMS, "url is missing"),
+        },
+        "search" => match list(&params, "query") {
+            words if !words.is_empty() => Command::SearchFor(words.join(" ")),
+            _ => return error(id, INVALID_PARAMS, "query is missing"),
+        },
+        _ => return error(id, METHOD_NOT_FOUND, &format!("unknown method: {}", method)),
+    };
+    match RXTX.deref().0.send(cmd).await {
+        Ok(()) => result(id, json!("ok")),
+        Err(e) => error(id, INTERNAL_ERROR, &e.to_string()),
+    }
+}
+
+/// by name, or by position
+fn param(params: &Value, name: &str, pos: usize) -> Option<String> {
+    let v = match params {
+        Value::Object(o) => o.get(name)?,
+        Value::Array(a) => a.get(pos)?,
+        _ => return None,
+    };
+    match v {
+        Value::String(s) => Some(s.to_owned()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// one by name, or all positional ones
+fn list(params: &Value, name: &str) -> Vec<String> {
+    match params {
+        Value::Array(a) => (0..a.len()).filter_map(|i| param(params, name, i)).collect(),
+        _ => param(params, name, 0).into_iter().collect(),
+    }
+}
+
+fn status(state: &SharedState) -> Value {
+    let current = state.get_current_track_info();
+    let queue = state
+        .peek_tracks(usize::MAX)
+        .into_iter()
+        .map(|x| track(&x.0))
+        .collect::<Vec<_>>();
+    let playing = match current.url.is_empty() {
+        true => Value::Null,
+        false => json!({
+            "track": current.track,
+            "artist": current.artist_name,
+            "album": current.album_title,
+            "url": current.url,
+            "item_url": current.results.item_url(),
+            "duration": current.duration,
+            "position": get_progress_bar_position(),
+            "play_date": current.play_date.to_rfc3339(),
+        }),
+    };
+    json!({
+        "current": playing,
+        "queue": queue,
+        "station": state.get_station().name(),
+        "mode": mode::label(&state.get_mode()),
+    })
+}
+
+fn track(t: &Track) -> Value {
+    json!({
+        "track": t.track,
+        "artist": t.artist_name,
+        "album": t.album_title,
+        "url": t.url,
+        "duration": t.duration,
+    })
+}
+
+fn result(id: Value, result: Value) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "result": result})
+}
+
+fn error(id: Value, code: i64, message: &str) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+/// `bcradio ctl <method> [params..]`, prints the result of the running instance
+pub fn ctl(method: &str, params: &[String]) -> Result<()> {
+    let path = args_socket();
+    let mut stream = net::UnixStream::connect(&path)
+        .map_err(|e| Error::msg(format!("no running instance on {}: {}", path.display(), e)))?;
+    let req = json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params});
+    stream.write_all(format!("{}\n", req).as_bytes())?;
+
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    let res = serde_json::from_str::<Value>(&line)?;
+    if let Some(e) = res.get("error") {
+        let message = e.get("message").and_then(Value::as_str).unwrap_or_default();
+        return Err(Error::msg(message.to_string()));
+    }
+    println!("{}", serde_json::to_string_pretty(&res["result"])?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{list, param};
+
+    #[test]
+    fn test_param() {
+        assert_eq!(param(&json!({"level": 5}), "level", 0), Some("5".to_string()));
+        assert_eq!(param(&json!(["5"]), "level", 0), Some("5".to_string()));
+        assert_eq!(param(&json!(["a", "b"]), "url", 1), Some("b".to_string()));
+        assert_eq!(param(&json!(null), "url", 0), None);
+        assert_eq!(list(&json!(["a", "b"]), "query"), ["a", "b"]);
+        assert_eq!(list(&json!({"query": "a b"}), "query"), ["a b"]);
+    }
+}
@@ -0,0 +1,238 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Error, Result};
+use rodio::{OutputStreamHandle, Sample, Sink, Source};
+
+use crate::models::shared_data_models::Track;
+
+pub type BoxSource = Box<dyn Source<Item = i16> + Send>;
+
+/// Two sinks: the active one plays the current track (and the next one,
+/// queued behind it for gapless playback), the other one is where the
+/// previous track fades out during a crossfade.
+pub struct Deck {
+    sinks: [Sink; 2],
+    active: usize,
+    /// fade-out trigger of the current track
+    current: Arc<AtomicU64>,
+    /// queued behind the current track, not on screen yet
+    pending: Option<(Track, Arc<AtomicU64>)>,
+    /// a track was started and has not been stopped
+    playing: bool,
+    /// stopped after a track, the next one waits for `play`
+    held: bool,
+}
+
+impl Deck {
+    pub fn try_new(handle: &OutputStreamHandle) -> Result<Deck> {
+        Ok(Deck {
+            sinks: [Sink::try_new(handle)?, Sink::try_new(handle)?],
+            active: 0,
+            current: Default::default(),
+            pending: None,
+            playing: false,
+            held: false,
+        })
+    }
+
+    /// nothing is playing on the active sink, a fading track may still be audible
+    pub fn empty(&self) -> bool {
+        self.sinks[self.active].empty()
+    }
+
+    pub fn has_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    pub fn play_now(&mut self, source: BoxSource) {
+        let (source, ctl) = Fader::new(source);
+        self.sinks[self.active].append(source);
+        self.current = ctl;
+        self.playing = true;
+    }
+
+    /// true once, when the last track has run out
+    pub fn ended(&mut self) -> bool {
+        if self.playing && self.empty() {
+            self.playing = false;
+            return true;
+        }
+        false
+    }
+
+    /// gapless when `fade` is zero, the track becomes current once the sink reaches it.
+    /// otherwise the current track fades out while `source` fades in on the other sink,
+    /// and `track` is returned as it is audible right away
+    pub fn queue_next(&mut self, track: Track, source: BoxSource, fade: Duration) -> Option<Track> {
+        if fade.is_zero() {
+            let (source, ctl) = Fader::new(source);
+            self.sinks[self.active].append(source);
+            self.pending = Some((track, ctl));
+            return None;
+        }
+
+        self.current.store(fade.as_millis() as u64, Ordering::Relaxed);
+        self.active = 1 - self.active;
+        self.sinks[self.active].stop();
+        self.play_now(Box::new(source.fade_in(fade)));
+        Some(track)
+    }
+
+    /// the queued track once the previous one has finished
+    pub fn advanced(&mut self) -> Option<Track> {
+        if self.pending.is_some() && self.sinks[self.active].len() <= 1 {
+            let (track, ctl) = self.pending.take()?;
+            self.current = ctl;
+            return Some(track);
+        }
+        None
+    }
+
+    /// skip the current track, a queued one starts right away
+    pub fn skip(&self) {
+        match self.pending {
+            Some(_) => self.sinks[self.active].skip_one(),
+            None => self.sinks[self.active].stop(),
+        }
+    }
+
+    /// stop both sinks, a queued track included
+    pub fn stop(&mut self) {
+        self.sinks.iter().for_each(|x| x.stop());
+        self.pending = None;
+        self.playing = false;
+    }
+
+    /// seek within the current track, the decoder must be over a seekable buffer
+    pub fn seek(&self, pos: Duration) -> Result<()> {
+        // blocks until the audio thread has performed it
+        tokio::task::block_in_place(|| self.sinks[self.active].try_seek(pos))
+            .map_err(|e| Error::msg(e.to_string()))
+    }
+
+    pub fn set_volume(&self, volume: f32) {
+        self.sinks.iter().for_each(|x| x.set_volume(volume));
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.held || self.sinks[self.active].is_paused()
+    }
+
+    pub fn pause(&self) {
+        self.sinks.iter().for_each(|x| x.pause());
+    }
+
+    pub fn play(&mut self) {
+        self.held = false;
+        self.sinks.iter().for_each(|x| x.play());
+    }
+
+    /// keep the next track from starting until `play`
+    pub fn hold(&mut self) {
+        self.held = true;
+    }
+
+    pub fn is_held(&self) -> bool {
+        self.held
+    }
+}
+
+/// fades out and ends the source once its control is set to a fade length in ms
+pub struct Fader<S> {
+    input: S,
+    ctl: Arc<AtomicU64>,
+    /// (fade length, samples left) in samples
+    fade: Option<(u64, u64)>,
+}
+
+impl<S> Fader<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    pub fn new(input: S) -> (Fader<S>, Arc<AtomicU64>) {
+        let ctl = Arc::new(AtomicU64::new(0));
+        let fader = Fader {
+            input,
+            ctl: Arc::clone(&ctl),
+            fade: None,
+        };
+        (fader, ctl)
+    }
+}
+
+impl<S> Iterator for Fader<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    type Item = S::Item;
+
+    fn next(&mut self) -> Option<S::Item> {
+        if self.fade.is_none() {
+            let ms = self.ctl.load(Ordering::Relaxed);
+            if ms > 0 {
+                let rate = self.input.sample_rate() as u64 * self.input.channels() as u64;
+                let len = (rate * ms / 1000).max(1);
+                self.fade = Some((len, len));
+            }
+        }
+        let sample = self.input.next()?;
+        match &mut self.fade {
+            None => Some(sample),
+            Some((_, 0)) => None,
+            Some((len, left)) => {
+                let gain = *left as f32 / *len as f32;
+                *left -= 1;
+                Some(sample.amplify(gain))
+            }
+        }
+    }
+}
+
+impl<S> Source for Fader<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), rodio::source::SeekError> {
+        self.input.try_seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use rodio::buffer::SamplesBuffer;
+
+    use super::Fader;
+
+    #[test]
+    fn test_fader() {
+        let (mut fader, ctl) = Fader::new(SamplesBuffer::new(1, 1000, vec![1000i16; 100]));
+        assert_eq!(fader.by_ref().take(10).collect::<Vec<_>>(), vec![1000; 10]);
+
+        // 5ms at 1kHz mono
+        ctl.store(5, Ordering::Relaxed);
+        assert_eq!(fader.collect::<Vec<_>>(), vec![1000, 800, 600, 400, 200]);
+    }
+}
@@ -0,0 +1,263 @@
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use anyhow::{Error, Result};
+use chrono::Local;
+use clap::ValueEnum;
+use crossterm::execute;
+use crossterm::terminal::{enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use inquire::Select;
+use serde::Serialize;
+
+use crate::vec_of_strings;
+use crate::libbc::config::data_dir;
+use crate::libbc::favorites::load_favorites;
+use crate::libbc::history::load_history;
+use crate::libbc::player::park_lock;
+use crate::libbc::progress_bar::println;
+use crate::libbc::shared_data::SharedState;
+use crate::libbc::terminal::clear_screen;
+use crate::models::history_models::HistoryRecord;
+use crate::models::shared_data_models::Track;
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ExportSource {
+    Queue,
+    History,
+    Favorites,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ExportFormat {
+    M3u8,
+    Xspf,
+    Json,
+}
+
+impl ExportFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::M3u8 => "m3u8",
+            ExportFormat::Xspf => "xspf",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportEntry {
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub genre: Option<String>,
+    pub duration: f32,
+    /// stream url or local file
+    pub location: String,
+    pub item_url: Option<String>,
+}
+
+impl From<&Track> for ExportEntry {
+    fn from(t: &Track) -> Self {
+        ExportEntry {
+            title: t.track.to_owned(),
+            artist: t.artist_name.to_owned(),
+            album: t.album_title.to_owned(),
+            genre: t.genre.to_owned(),
+            duration: t.duration,
+            location: t.url.to_owned(),
+            item_url: t.results.item_url(),
+        }
+    }
+}
+
+impl From<&HistoryRecord> for ExportEntry {
+    fn from(t: &HistoryRecord) -> Self {
+        ExportEntry::from(&t.to_track())
+    }
+}
+
+pub trait Export {
+    /// export from the player, asks source and format
+    fn export(&self) -> Result<()>;
+}
+
+impl Export for SharedState {
+    fn export(&self) -> Result<()> {
+        park_lock();
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        enable_raw_mode()?;
+        execute!(stdout, EnterAlternateScreen)?;
+        clear_screen();
+        let res = ask_export();
+        execute!(stdout, LeaveAlternateScreen)?;
+
+        if let Some((source, format)) = res? {
+            let entries = match source {
+                ExportSource::Queue => self.get_tracklist().iter().map(ExportEntry::from).collect(),
+                _ => load_entries(source)?,
+            };
+            let path = write(&entries, source, format, None)?;
+            println(&format!("exported: {}", path.display()));
+        }
+        Ok(())
+    }
+}
+
+fn ask_export() -> Result<Option<(ExportSource, ExportFormat)>> {
+    let source = Select::new("export?", vec_of_strings!["queue", "history", "favorites"]).prompt();
+    let source = match source {
+        Ok(a) => ExportSource::from_str(&a, true).map_err(Error::msg)?,
+        Err(_) => return Ok(None),
+    };
+    let format = Select::new("format?", vec_of_strings!["m3u8", "xspf", "json"]).prompt();
+    let format = match format {
+        Ok(a) => ExportFormat::from_str(&a, true).map_err(Error::msg)?,
+        Err(_) => return Ok(None),
+    };
+    Ok(Some((source, format)))
+}
+
+/// history and favorites, the queue only exists while playing
+pub fn load_entries(source: ExportSource) -> Result<Vec<ExportEntry>> {
+    Ok(match source {
+        ExportSource::Queue => {
+            return Err(Error::msg("the queue can only be exported while playing (key: e)"))
+        }
+        ExportSource::History => load_history()?.iter().map(ExportEntry::from).collect(),
+        ExportSource::Favorites => load_favorites()?
+            .iter()
+            .map(|x| ExportEntry::from(&x.track))
+            .collect(),
+    })
+}
+
+/// `-` writes to stdout
+pub fn write(
+    entries: &[ExportEntry],
+    source: ExportSource,
+    format: ExportFormat,
+    path: Option<PathBuf>,
+) -> Result<PathBuf> {
+    let path = path.unwrap_or_else(|| {
+        data_dir().join("exports").join(format!(
+            "bcradio-{}-{}.{}",
+            source.to_possible_value().unwrap().get_name(),
+            Local::now().format("%Y%m%d-%H%M%S"),
+            format.extension()
+        ))
+    });
+    let s = render(entries, format)?;
+    if path.as_os_str() == "-" {
+        print!("{s}");
+        return Ok(path);
+    }
+    if let Some(dir) = path.parent().filter(|x| !x.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, s)?;
+    Ok(path)
+}
+
+pub fn render(entries: &[ExportEntry], format: ExportFormat) -> Result<String> {
+    let mut s = String::new();
+    match format {
+        ExportFormat::M3u8 => {
+            writeln!(s, "#EXTM3U")?;
+            for e in entries {
+                writeln!(s, "#EXTINF:{},{} - {}", e.duration.round() as i64, e.artist, e.title)?;
+                writeln!(s, "#EXTALB:{}", e.album)?;
+                if let Some(genre) = &e.genre {
+                    writeln!(s, "#EXTGENRE:{}", genre)?;
+                }
+                // stream urls expire, the item page does not
+                writeln!(s, "{}", e.item_url.as_ref().unwrap_or(&e.location))?;
+            }
+        }
+        ExportFormat::Xspf => {
+            writeln!(s, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+            writeln!(s, r#"<playlist version="1" xmlns="http://xspf.org/ns/0/">"#)?;
+            writeln!(s, "  <trackList>")?;
+            for e in entries {
+                writeln!(s, "    <track>")?;
+                writeln!(s, "      <location>{}</location>", xml_escape(&e.location))?;
+                if let Some(item_url) = &e.item_url {
+                    writeln!(s, "      <info>{}</info>", xml_escape(item_url))?;
+                }
+                writeln!(s, "      <title>{}</title>", xml_escape(&e.title))?;
+                writeln!(s, "      <creator>{}</creator>", xml_escape(&e.artist))?;
+                writeln!(s, "      <album>{}</album>", xml_escape(&e.album))?;
+                if let Some(genre) = &e.genre {
+                    writeln!(s, "      <annotation>{}</annotation>", xml_escape(genre))?;
+                }
+                writeln!(s, "      <duration>{}</duration>", (e.duration * 1000.0) as u64)?;
+                writeln!(s, "    </track>")?;
+            }
+            writeln!(s, "  </trackList>")?;
+            writeln!(s, "</playlist>")?;
+        }
+        ExportFormat::Json => {
+            s = serde_json::to_string_pretty(entries)?;
+            s.push('\n');
+        }
+    }
+    Ok(s)
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+pub fn export_cli(source: ExportSource, format: ExportFormat, path: Option<PathBuf>) -> Result<()> {
+    let entries = load_entries(source)?;
+    if entries.is_empty() {
+        return Err(Error::msg("nothing to export"));
+    }
+    let path = write(&entries, source, format, path)?;
+    if path.as_os_str() != "-" {
+        eprintln!("exported: {}", path.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, ExportEntry, ExportFormat};
+
+    fn entry() -> ExportEntry {
+        ExportEntry {
+            title: "Rock & Roll".to_string(),
+            artist: "<artist>".to_string(),
+            album: "album".to_string(),
+            genre: Some("jazz".to_string()),
+            duration: 180.4,
+            location: "https://t4.bcbits.com/stream/a/mp3-128/1?p=0&t=x".to_string(),
+            item_url: Some("https://artist.bandcamp.com/track/rock-roll".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_m3u8() {
+        let s = render(&[entry()], ExportFormat::M3u8).unwrap();
+        assert_eq!(
+            s,
+            "#EXTM3U\n#EXTINF:180,<artist> - Rock & Roll\n#EXTALB:album\n#EXTGENRE:jazz\n\
+             https://artist.bandcamp.com/track/rock-roll\n"
+        );
+    }
+
+    #[test]
+    fn test_xspf() {
+        let s = render(&[entry()], ExportFormat::Xspf).unwrap();
+        assert!(s.contains("<title>Rock &amp; Roll</title>"));
+        assert!(s.contains("<creator>&lt;artist&gt;</creator>"));
+        assert!(s.contains("<location>https://t4.bcbits.com/stream/a/mp3-128/1?p=0&amp;t=x</location>"));
+        assert!(s.contains("<duration>180400</duration>"));
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use rand::seq::SliceRandom;
+
+use crate::libbc::config::data_dir;
+use crate::libbc::playlist::format;
+use crate::libbc::progress_bar::println;
+use crate::libbc::shared_data::SharedState;
+use crate::libbc::terminal::{show_alt_term3, ListAction};
+use crate::models::favorites_models::FavoriteRecord;
+use crate::models::shared_data_models::Track;
+
+pub trait Favorites {
+    /// star or unstar the current track
+    fn toggle_favorite(&self) -> Result<()>;
+    fn show_favorites(&self) -> Result<()>;
+    /// favorites station
+    fn append_favorites(&self) -> Result<()>;
+}
+
+impl Favorites for SharedState {
+    fn toggle_favorite(&self) -> Result<()> {
+        let current = self.get_current_track_info();
+        if current.url.is_empty() {
+            return Ok(());
+        }
+        let track = current.to_track();
+
+        let mut favorites = load_favorites()?;
+        match favorites.iter().position(|x| x.is(&track)) {
+            Some(pos) => {
+                favorites.remove(pos);
+                println(&format!("☆ unstarred: {}", track.track));
+            }
+            None => {
+                println(&format!("★ starred: {}", track.track));
+                favorites.push(FavoriteRecord::new(track));
+            }
+        }
+        save_favorites(&favorites)
+    }
+
+    fn show_favorites(&self) -> Result<()> {
+        loop {
+            let mut favorites = load_favorites()?;
+
+            let mut v = vec![format!(
+                "{:>2} {:30} {:>7} {:30} {}   (play:enter, remove:d)",
+                "#", "Track", "Time", "Artist", "Album"
+            )];
+            v.extend(
+                favorites
+                    .iter()
+                    .enumerate()
+                    .map(|(n, x)| format(n + 1, &x.track)),
+            );
+
+            match show_alt_term3(&v, true)? {
+                Some(ListAction::Select(l)) => {
+                    let track = favorites[l - 1].track.to_owned();
+                    self.mark_stale(&track.url);
+                    self.push_front_tracklist(track);
+                    return Ok(());
+                }
+                Some(ListAction::Delete(l)) => {
+                    favorites.remove(l - 1);
+                    save_favorites(&favorites)?;
+                }
+                None => return Ok(()),
+            }
+        }
+    }
+
+    fn append_favorites(&self) -> Result<()> {
+        let mut tracks = load_favorites()?
+            .into_iter()
+            .map(|x| x.track)
+            .collect::<Vec<Track>>();
+        tracks.shuffle(&mut rand::thread_rng());
+        tracks.iter().for_each(|x| self.mark_stale(&x.url));
+        self.append_tracklist(VecDeque::from(tracks));
+        Ok(())
+    }
+}
+
+fn favorites_path() -> PathBuf {
+    data_dir().join("favorites.json")
+}
+
+pub fn load_favorites() -> Result<Vec<FavoriteRecord>> {
+    let path = favorites_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(serde_json::from_slice(&fs::read(path)?)?)
+}
+
+fn save_favorites(favorites: &[FavoriteRecord]) -> Result<()> {
+    let path = favorites_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_vec_pretty(favorites)?)?;
+    Ok(())
+}
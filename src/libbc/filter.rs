@@ -0,0 +1,139 @@
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime};
+
+use crate::libbc::config::FilterConfig;
+use crate::models::bc_discover_json::Results;
+
+/// discover items that pass the price and release date filters
+pub fn accept(item: &Results, filter: &FilterConfig, today: NaiveDate) -> bool {
+    let price = &item.price;
+    if filter.free_only && price.amount > 0 {
+        return false;
+    }
+    if filter
+        .currency
+        .as_ref()
+        .is_some_and(|c| !c.eq_ignore_ascii_case(&price.currency))
+    {
+        return false;
+    }
+    if filter
+        .max_price
+        .is_some_and(|max| price.amount as f64 / minor_units(&price.currency) > max)
+    {
+        return false;
+    }
+
+    if filter.released_within_days.is_none()
+        && filter.released_after.is_none()
+        && filter.released_before.is_none()
+    {
+        return true;
+    }
+    let Some(date) = parse_date(&item.release_date) else {
+        return false;
+    };
+    if let Some(days) = filter.released_within_days {
+        if date < today - Duration::days(days) {
+            return false;
+        }
+    }
+    if filter.released_after.is_some_and(|x| date < x) {
+        return false;
+    }
+    if filter.released_before.is_some_and(|x| date > x) {
+        return false;
+    }
+    true
+}
+
+/// amounts are in cents, yen have none
+fn minor_units(currency: &str) -> f64 {
+    match currency.to_uppercase().as_str() {
+        "JPY" => 1.0,
+        _ => 100.0,
+    }
+}
+
+/// "15 Mar 2024 00:00:00 GMT", rfc3339 or "2024-03-15"
+pub fn parse_date(s: &str) -> Option<NaiveDate> {
+    let s = s.trim().trim_matches('"');
+    if let Ok(d) = DateTime::parse_from_rfc3339(s) {
+        return Some(d.date_naive());
+    }
+    ["%d %b %Y %H:%M:%S GMT", "%Y-%m-%d %H:%M:%S UTC", "%Y-%m-%d %H:%M:%S"]
+        .iter()
+        .find_map(|f| NaiveDateTime::parse_from_str(s, f).ok())
+        .map(|d| d.date())
+        .or_else(|| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::{accept, parse_date};
+    use crate::libbc::config::FilterConfig;
+    use crate::models::bc_discover_json::Results;
+
+    fn item(amount: i64, currency: &str, release_date: &str) -> Results {
+        let mut r = Results::default();
+        r.price.amount = amount;
+        r.price.currency = currency.to_string();
+        r.release_date = release_date.to_string();
+        r
+    }
+
+    #[test]
+    fn test_accept() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 20).unwrap();
+        let a = item(700, "USD", "15 Mar 2024 00:00:00 GMT");
+        let b = item(0, "EUR", "2023-01-01");
+
+        let f = FilterConfig {
+            currency: Some("eur".to_string()),
+            ..Default::default()
+        };
+        assert!(!accept(&a, &f, today));
+        assert!(accept(&b, &f, today));
+
+        let f = FilterConfig {
+            max_price: Some(1500.0),
+            currency: Some("JPY".to_string()),
+            ..Default::default()
+        };
+        assert!(accept(&item(1000, "JPY", ""), &f, today));
+        assert!(!accept(&item(2000, "JPY", ""), &f, today));
+
+        let f = FilterConfig {
+            max_price: Some(10.0),
+            currency: Some("usd".to_string()),
+            ..Default::default()
+        };
+        assert!(accept(&a, &f, today));
+        assert!(!accept(&b, &f, today));
+
+        let f = FilterConfig {
+            free_only: true,
+            ..Default::default()
+        };
+        assert!(!accept(&a, &f, today));
+        assert!(accept(&b, &f, today));
+
+        let f = FilterConfig {
+            released_within_days: Some(7),
+            ..Default::default()
+        };
+        assert!(accept(&a, &f, today));
+        assert!(!accept(&b, &f, today));
+
+        let f = FilterConfig {
+            released_after: NaiveDate::from_ymd_opt(2022, 12, 1),
+            released_before: NaiveDate::from_ymd_opt(2023, 12, 31),
+            ..Default::default()
+        };
+        assert!(!accept(&a, &f, today));
+        assert!(accept(&b, &f, today));
+        assert!(!accept(&item(0, "USD", ""), &f, today));
+        assert_eq!(parse_date("2024-03-15T10:00:00Z"), NaiveDate::from_ymd_opt(2024, 3, 15));
+    }
+}
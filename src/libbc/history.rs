@@ -0,0 +1,91 @@
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+use anyhow::Result;
+use log::error;
+
+use crate::libbc::config::data_dir;
+use crate::libbc::playlist::format;
+use crate::libbc::progress_bar::get_progress_bar_position;
+use crate::libbc::shared_data::SharedState;
+use crate::libbc::terminal::show_alt_term2;
+use crate::models::history_models::{HistoryRecord, PlayStatus};
+
+pub trait History {
+    fn record_history(&self);
+    fn show_history(&self) -> Result<()>;
+}
+
+impl History for SharedState {
+    /// append the track that is about to be replaced to the history log
+    fn record_history(&self) {
+        let current = self.get_current_track_info();
+        if current.url.is_empty() {
+            return;
+        }
+        let status = if self.is_skipped() {
+            PlayStatus::Skipped
+        } else {
+            PlayStatus::Finished
+        };
+
+        let record = HistoryRecord::new(&current, status, get_progress_bar_position());
+        if let Err(e) = append_history(&record) {
+            error!("history: {}\r", e);
+        }
+    }
+
+    fn show_history(&self) -> Result<()> {
+        // the whole log, newest first
+        let records = load_history()?.into_iter().rev().collect::<Vec<_>>();
+
+        // s: skipped
+        let mut v = vec![format!(
+            "{:11} {:1} {:>2} {:30} {:>7} {:30} {}",
+            "Played", "", "#", "Track", "Time", "Artist", "Album"
+        )];
+        v.extend(records.iter().enumerate().map(|(n, x)| {
+            format!(
+                "{} {:1} {}",
+                x.played_at.format("%m/%d %H:%M"),
+                if x.status == PlayStatus::Skipped { "s" } else { "" },
+                format(n + 1, &x.to_track())
+            )
+        }));
+
+        if let Some(l) = show_alt_term2(&v)? {
+            let track = records[l - 1].to_track();
+            self.mark_stale(&track.url);
+            self.push_front_tracklist(track);
+        }
+        Ok(())
+    }
+}
+
+fn history_path() -> PathBuf {
+    data_dir().join("history.jsonl")
+}
+
+pub fn append_history(record: &HistoryRecord) -> Result<()> {
+    let path = history_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut f = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(f, "{}", serde_json::to_string(record)?)?;
+    Ok(())
+}
+
+pub fn load_history() -> Result<Vec<HistoryRecord>> {
+    let path = history_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let f = fs::File::open(path)?;
+    Ok(BufReader::new(f)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|l| serde_json::from_str(&l).ok())
+        .collect())
+}
@@ -0,0 +1,157 @@
+use std::process::Stdio;
+
+use anyhow::{Error, Result};
+use log::warn;
+use serde_json::{json, Value};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::libbc::args::args_hooks;
+use crate::libbc::progress_bar::get_progress_bar_position;
+use crate::models::shared_data_models::Track;
+
+/// player events that run the user command configured in `[hooks]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    TrackStart,
+    TrackEnd,
+    Pause,
+    Resume,
+    Error,
+}
+
+impl Hook {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Hook::TrackStart => "track_start",
+            Hook::TrackEnd => "track_end",
+            Hook::Pause => "pause",
+            Hook::Resume => "resume",
+            Hook::Error => "error",
+        }
+    }
+
+    fn command(&self) -> Option<String> {
+        let hooks = args_hooks();
+        match self {
+            Hook::TrackStart => hooks.on_track_start,
+            Hook::TrackEnd => hooks.on_track_end,
+            Hook::Pause => hooks.on_pause,
+            Hook::Resume => hooks.on_resume,
+            Hook::Error => hooks.on_error,
+        }
+        .filter(|x| !x.trim().is_empty())
+    }
+}
+
+/// run the command of `hook` in the background, `track` is in the environment and as JSON on stdin
+pub fn run(hook: Hook, track: &Track, error: Option<&str>) {
+    let Some(cmd) = hook.command() else {
+        return;
+    };
+    let position = get_progress_bar_position();
+    let env = env(hook, track, position, error);
+    let payload = payload(hook, track, position, error);
+    tokio::spawn(async move {
+        if let Err(e) = exec(&cmd, env, payload).await {
+            warn!("hook {}: {}\r", hook.name(), e);
+        }
+    });
+}
+
+async fn exec(cmd: &str, env: Vec<(&'static str, String)>, payload: Value) -> Result<()> {
+    let mut child = shell(cmd)
+        .envs(env)
+        .stdin(Stdio::piped())
+        // the terminal belongs to the player
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // the command may not read it
+        let _ = stdin.write_all(format!("{}\n", payload).as_bytes()).await;
+    }
+    let status = child.wait().await?;
+    if !status.success() {
+        return Err(Error::msg(format!("`{}` {}", cmd, status)));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn shell(cmd: &str) -> Command {
+    let mut c = Command::new("sh");
+    c.arg("-c").arg(cmd);
+    c
+}
+
+#[cfg(windows)]
+fn shell(cmd: &str) -> Command {
+    let mut c = Command::new("cmd");
+    c.arg("/C").arg(cmd);
+    c
+}
+
+fn env(hook: Hook, t: &Track, position: u64, error: Option<&str>) -> Vec<(&'static str, String)> {
+    let mut v = vec![
+        ("BCRADIO_EVENT", hook.name().to_string()),
+        ("BCRADIO_TITLE", t.track.to_owned()),
+        ("BCRADIO_ARTIST", t.artist_name.to_owned()),
+        ("BCRADIO_ALBUM", t.album_title.to_owned()),
+        ("BCRADIO_URL", t.url.to_owned()),
+        ("BCRADIO_ITEM_URL", t.results.item_url().unwrap_or_default()),
+        ("BCRADIO_ART_ID", t.art_id.map(|x| x.to_string()).unwrap_or_default()),
+        ("BCRADIO_BAND_ID", t.band_id.to_string()),
+        ("BCRADIO_DURATION", format!("{:.0}", t.duration)),
+        ("BCRADIO_POSITION", position.to_string()),
+    ];
+    if let Some(e) = error {
+        v.push(("BCRADIO_ERROR", e.to_string()));
+    }
+    v
+}
+
+fn payload(hook: Hook, t: &Track, position: u64, error: Option<&str>) -> Value {
+    json!({
+        "event": hook.name(),
+        "track": {
+            "title": t.track,
+            "artist": t.artist_name,
+            "album": t.album_title,
+            "url": t.url,
+            "item_url": t.results.item_url(),
+            "art_id": t.art_id,
+            "band_id": t.band_id,
+            "duration": t.duration,
+            "genre": t.genre,
+            "subgenre": t.subgenre,
+        },
+        "position": position,
+        "error": error,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{env, Hook};
+    use crate::models::shared_data_models::Track;
+
+    #[test]
+    fn test_env() {
+        let t = Track {
+            track: "a".to_string(),
+            artist_name: "b".to_string(),
+            art_id: Some(7),
+            duration: 61.4,
+            ..Default::default()
+        };
+        let v = env(Hook::TrackEnd, &t, 30, None);
+        let get = |k: &str| v.iter().find(|x| x.0 == k).map(|x| x.1.as_str());
+        assert_eq!(get("BCRADIO_EVENT"), Some("track_end"));
+        assert_eq!(get("BCRADIO_TITLE"), Some("a"));
+        assert_eq!(get("BCRADIO_ART_ID"), Some("7"));
+        assert_eq!(get("BCRADIO_DURATION"), Some("61"));
+        assert_eq!(get("BCRADIO_POSITION"), Some("30"));
+        assert_eq!(get("BCRADIO_ERROR"), None);
+    }
+}
@@ -1,9 +1,10 @@
 use crate::libbc::search::{base_url, parse_doc};
 use crate::models::search_models::{Current, ItemPage, TrackInfo};
-use crate::models::shared_data_models::Track;
+use crate::models::shared_data_models::{ResultsJson, Track};
 use anyhow::{anyhow, Result};
 use bytes::{Bytes, BytesMut};
-use futures::{stream, StreamExt, TryStreamExt};
+use futures::{stream, StreamExt};
+use log::info;
 use reqwest::{header};
 use scraper::Html;
 use simd_json::prelude::{ValueAsScalar, ValueObjectAccess};
@@ -18,6 +19,24 @@ pub async fn http_adapter<R>(
     urls: Vec<String>,
     plug: FA<impl Future<Output = Result<Vec<R>>> + Send + 'static>,
 ) -> Result<Vec<R>>
+where
+    R: Send + 'static,
+{
+    let mut acc = Vec::<R>::new();
+    for (url, res) in http_adapter_each(urls, plug).await? {
+        match res {
+            Ok(mut x) => acc.append(&mut x),
+            Err(e) => info!("skip {} {}\r", url, e),
+        }
+    }
+    Ok(acc)
+}
+
+/// result per url, in the order of `urls`
+pub async fn http_adapter_each<R>(
+    urls: Vec<String>,
+    plug: FA<impl Future<Output = Result<Vec<R>>> + Send + 'static>,
+) -> Result<Vec<(String, Result<Vec<R>>)>>
 where
     R: Send + 'static,
 {
@@ -30,31 +49,27 @@ where
     headers.insert("Content-Encoding", header::HeaderValue::from_static("gzip"));
     let client = client_builder(headers)?;
 
-    stream::iter(urls)
+    Ok(stream::iter(urls)
         .map(|url| {
             let client = client.clone();
             tokio::spawn(async move {
-                match client.get(url).send().await {
+                let res = match client.get(&url).send().await {
                     Ok(r) => match r.error_for_status() {
-                        Ok(res) => Ok(res),
+                        Ok(res) => match res.bytes().await {
+                            Ok(v) => plug(v).await,
+                            Err(e) => Err(anyhow!("response: {}", e)),
+                        },
                         Err(e) => Err(anyhow!("status: {}", e)),
                     },
                     Err(e) => Err(anyhow!("response: {}", e)),
-                }
+                };
+                (url, res)
             })
         })
-        .buffer_unordered(PARALLEL_REQUESTS)
-        .filter_map(|x| async move { x.ok()?.ok() })
-        .map(move |v| tokio::spawn(async move { plug(v.bytes().await?).await }))
-        .buffer_unordered(PARALLEL_REQUESTS)
+        .buffered(PARALLEL_REQUESTS)
         .filter_map(|x| async move { x.ok() })
-        .try_fold(Vec::<R>::new(), |mut acc, x| async move {
-            for t in x {
-                acc.push(t);
-            }
-            Result::<Vec<R>>::Ok(acc)
-        })
-        .await
+        .collect::<Vec<_>>()
+        .await)
 }
 
 pub async fn html_to_track(v: Bytes) -> Result<Vec<Track>> {
@@ -81,14 +96,16 @@ pub fn html_to_json(res: Vec<u8>) -> Result<Value> {
 pub fn j2t(json: Value) -> Result<Vec<Track>> {
     let item_url = json["url"].to_string();
     let base_item_url = base_url(&item_url);
-    let item_path = match json.get("album_url") {
+    let item_path = match json.get("album_url").and_then(|a| a.as_str()) {
         Some(a) => {
-            if !a.to_string().is_empty() && !base_item_url.is_empty() {
+            if !a.is_empty() && !base_item_url.is_empty() {
                 format!("{}{}", base_item_url, a)
             } else {
                 String::from("")
             }
         }
+        // an album page is its own release
+        None if item_url.contains("/album/") => item_url.to_owned(),
         None => String::from(""),
     };
 
@@ -119,10 +136,17 @@ pub fn j2t(json: Value) -> Result<Vec<Track>> {
             url: i.clone().file.unwrap().mp3_128.unwrap(),
             duration: i.duration,
             track: i.title.to_owned().unwrap(),
-            // buffer: vec![],
-            // results: ResultsJson::Search(Box::new(tracks.clone())),
-            // genre: None,
-            // subgenre: None,
+            // the track page, without the whole tracklist
+            results: ResultsJson::Search(Box::new(ItemPage {
+                current: tracks.current.clone(),
+                artist: tracks.artist.to_owned(),
+                trackinfo: vec![],
+                album_url: tracks.album_url.clone(),
+                item_url: match (&i.title_link, base_item_url.is_empty()) {
+                    (Some(link), false) => Some(format!("{}{}", base_item_url, link)),
+                    _ => tracks.item_url.clone(),
+                },
+            })),
             ..Default::default()
         };
         v.push(t);
@@ -1,12 +1,18 @@
 use std::time::Duration;
 use anyhow::{Error, Result};
 use async_std::task::block_on;
+use bytes::Bytes;
+use futures::stream::{self, BoxStream};
+use futures::StreamExt;
 use reqwest::{header, Client};
 use serde::Serialize;
 use log::{error, info};
 use reqwest::header::HeaderMap;
 use crate::libbc::args::{args_no_ssl_verify, args_socks};
 
+/// a whole track on a slow link
+const STREAM_TIMEOUT: Duration = Duration::from_secs(3600);
+
 pub const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/69.0.3497.100";
 
 pub async fn get_request(url: &str) -> Result<Vec<u8>> {
@@ -40,6 +46,32 @@ pub async fn get_request(url: &str) -> Result<Vec<u8>> {
     Ok(res)
 }
 
+/// response body in chunks as it arrives, no overall timeout
+pub async fn get_stream(url: &str) -> Result<BoxStream<'static, Result<Bytes>>> {
+    info!("debug: get_stream {}\r", url);
+
+    let mut headers = header::HeaderMap::new();
+    headers.insert("Accept", header::HeaderValue::from_static("*/*"));
+
+    let client = client_builder(headers)?;
+    let r = client
+        .get(url)
+        .timeout(STREAM_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    Ok(stream::unfold(Some(r), |r| async move {
+        let mut r = r?;
+        match r.chunk().await {
+            Ok(Some(b)) => Some((Ok(b), Some(r))),
+            Ok(None) => None,
+            Err(e) => Some((Err(Error::from(e)), None)),
+        }
+    })
+    .boxed())
+}
+
 pub fn get_blocking_request(url: &str) -> Result<Vec<u8>> {
     info!("debug: get_blocking_request {}\r", url);
     let mut headers = header::HeaderMap::new();
@@ -117,6 +149,29 @@ where
     Ok(res)
 }
 
+/// JSON body with an `Authorization` header, an error status is an error
+pub async fn post_json<T>(url: &str, authorization: &str, post_data: &T) -> Result<()>
+where
+    T: Serialize,
+{
+    info!("debug: post_json {}\r", url);
+
+    let mut headers = header::HeaderMap::new();
+    headers.insert(
+        "Content-Type",
+        header::HeaderValue::from_static("application/json"),
+    );
+    headers.insert("Authorization", header::HeaderValue::from_str(authorization)?);
+
+    let client = client_builder(headers)?;
+    client
+        .post(url)
+        .body(serde_json::to_string(post_data)?)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
 
 pub fn client_builder(headers: HeaderMap) -> reqwest::Result<Client> {
     let mut no_ssl_verify = false;
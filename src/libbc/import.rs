@@ -0,0 +1,147 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::libbc::bandcamp_api::api;
+use crate::libbc::local;
+use crate::libbc::progress_bar::{disable_spinner, enable_spinner, println};
+use crate::libbc::shared_data::SharedState;
+use crate::models::shared_data_models::Track;
+
+#[async_trait]
+pub trait Import {
+    /// resolve the playlist and append it to the queue, returns the number of queued tracks
+    async fn import(&self, path: &Path) -> Result<usize>;
+    /// resolve bandcamp urls or local files and append them to the queue
+    async fn enqueue(&self, entries: Vec<String>) -> Result<usize>;
+}
+
+#[async_trait]
+impl Import for SharedState {
+    async fn import(&self, path: &Path) -> Result<usize> {
+        self.enqueue(parse(&fs::read_to_string(path)?)).await
+    }
+
+    async fn enqueue(&self, entries: Vec<String>) -> Result<usize> {
+        enable_spinner();
+        let resolved = resolve(entries).await;
+        disable_spinner();
+
+        let mut tracks = VecDeque::new();
+        let mut failed = 0;
+        for (entry, res) in resolved {
+            match res {
+                Ok(mut t) if !t.is_empty() => tracks.append(&mut VecDeque::from(t.split_off(0))),
+                Ok(_) => {
+                    failed += 1;
+                    println(&format!("import: skip {} (no playable tracks)", entry));
+                }
+                Err(e) => {
+                    failed += 1;
+                    println(&format!("import: skip {} ({})", entry, e));
+                }
+            }
+        }
+
+        let n = tracks.len();
+        println(&format!("import: {} tracks queued, {} failed", n, failed));
+        self.append_tracklist(tracks);
+        Ok(n)
+    }
+}
+
+/// m3u / m3u8, plain text (one url per line) or json
+/// (array of urls, or objects with `item_url`, `url` or `location`)
+pub fn parse(s: &str) -> Vec<String> {
+    let t = s.trim_start();
+    if t.starts_with('[') || t.starts_with('{') {
+        if let Ok(json) = serde_json::from_str::<Value>(t) {
+            let items = match json {
+                Value::Array(a) => a,
+                v => vec![v],
+            };
+            return items
+                .iter()
+                .filter_map(|x| match x {
+                    Value::String(url) => Some(url.to_owned()),
+                    Value::Object(o) => ["item_url", "url", "location"]
+                        .iter()
+                        .find_map(|k| o.get(*k)?.as_str().map(str::to_string)),
+                    _ => None,
+                })
+                .collect();
+        }
+    }
+    s.lines()
+        .map(str::trim)
+        .filter(|x| !x.is_empty() && !x.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+async fn resolve(entries: Vec<String>) -> Vec<(String, Result<Vec<Track>>)> {
+    let urls = entries
+        .iter()
+        .filter(|x| x.starts_with("http://") || x.starts_with("https://"))
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut remote = match api().resolve(urls.clone()).await {
+        Ok(r) => r,
+        Err(e) => urls
+            .into_iter()
+            .map(|url| (url, Err(anyhow!("{}", e))))
+            .collect(),
+    };
+
+    entries
+        .into_iter()
+        .map(|entry| {
+            if entry.starts_with("http://") || entry.starts_with("https://") {
+                // a page that crashed the parser has no result
+                return match remote.iter().position(|x| x.0 == entry) {
+                    Some(i) => remote.remove(i),
+                    None => (entry, Err(anyhow!("unreadable page"))),
+                };
+            }
+            // local file
+            let res = match local::is_local(&entry) {
+                true => local::to_path(&entry),
+                false => Ok(PathBuf::from(&entry)),
+            }
+            .and_then(|path| local::track(&path))
+            .map(|t| vec![t]);
+            (entry, res)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+
+    #[test]
+    fn test_parse() {
+        let m3u = "#EXTM3U\n#EXTINF:180,a - b\n#EXTALB:c\nhttps://a.bandcamp.com/track/b\n\n \
+                   https://a.bandcamp.com/album/c\r\n";
+        assert_eq!(
+            parse(m3u),
+            vec!["https://a.bandcamp.com/track/b", "https://a.bandcamp.com/album/c"]
+        );
+
+        let json = r#"[{"title":"b","location":"https://t4.bcbits.com/x","item_url":"https://a.bandcamp.com/track/b"},
+                       {"location":"file:///music/a.flac","item_url":null},
+                       "https://a.bandcamp.com/album/c"]"#;
+        assert_eq!(
+            parse(json),
+            vec![
+                "https://a.bandcamp.com/track/b",
+                "file:///music/a.flac",
+                "https://a.bandcamp.com/album/c"
+            ]
+        );
+    }
+}
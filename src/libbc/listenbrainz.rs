@@ -0,0 +1,274 @@
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use chrono::Local;
+use log::{info, warn};
+use reqwest::StatusCode;
+use serde_json::{json, Map, Value};
+
+use crate::libbc::args::args_listenbrainz;
+use crate::libbc::config::{data_dir, ListenBrainzConfig};
+use crate::libbc::http_client::post_json;
+use crate::libbc::player::PROG;
+use crate::models::shared_data_models::CurrentTrack;
+
+const SUBMIT_PATH: &str = "/1/submit-listens";
+/// a listen counts after half the track, or this much of a long one
+const MAX_LISTEN: Duration = Duration::from_secs(240);
+/// how often listens that failed to submit are retried
+const RETRY: Duration = Duration::from_secs(300);
+/// listens per retry request
+const BATCH: usize = 100;
+
+/// the current track, as far as it has been played
+struct Listen {
+    metadata: Value,
+    listened_at: i64,
+    duration: f32,
+    played: Duration,
+    last: Instant,
+    submitted: bool,
+}
+
+static LISTEN: LazyLock<Mutex<Option<Listen>>> = LazyLock::new(|| Mutex::new(None));
+/// the retry queue file is rewritten by one task at a time
+static QUEUE: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// `current` has started, send it as playing now
+pub fn playing_now(current: &CurrentTrack) {
+    let Some(config) = args_listenbrainz() else {
+        return;
+    };
+    let mut lock = LISTEN.lock().unwrap();
+    *lock = None;
+    if current.artist_name.is_empty() || current.track.is_empty() {
+        // nothing to identify it by
+        return;
+    }
+    let metadata = track_metadata(current);
+    *lock = Some(Listen {
+        metadata: metadata.clone(),
+        listened_at: Local::now().timestamp(),
+        duration: current.duration,
+        played: Duration::ZERO,
+        last: Instant::now(),
+        submitted: false,
+    });
+    tokio::spawn(async move {
+        let listen = json!({ "track_metadata": metadata });
+        if let Err(e) = submit(&config, "playing_now", &[listen]).await {
+            warn!("listenbrainz: playing now {}\r", e);
+        }
+    });
+}
+
+/// count the playback since the last call, the listen is sent once it has been played long enough
+pub fn tick() {
+    let Some(config) = args_listenbrainz() else {
+        return;
+    };
+    let playing = *PROG.lock().unwrap();
+    let mut lock = LISTEN.lock().unwrap();
+    let Some(l) = lock.as_mut() else {
+        return;
+    };
+    let now = Instant::now();
+    if playing {
+        l.played += now - l.last;
+    }
+    l.last = now;
+    if l.submitted || l.played < threshold(l.duration) {
+        return;
+    }
+    l.submitted = true;
+    let listen = json!({ "listened_at": l.listened_at, "track_metadata": l.metadata });
+    tokio::spawn(async move {
+        match submit(&config, "single", std::slice::from_ref(&listen)).await {
+            Ok(()) => flush(&config).await,
+            Err(e) => {
+                warn!("listenbrainz: {}, queued for later\r", e);
+                let _guard = QUEUE.lock().await;
+                if let Err(e) = enqueue(&listen) {
+                    warn!("listenbrainz: {}\r", e);
+                }
+            }
+        }
+    });
+}
+
+/// retry the queued listens now and then
+pub async fn serve() {
+    let Some(config) = args_listenbrainz() else {
+        return;
+    };
+    let mut interval = tokio::time::interval(RETRY);
+    loop {
+        interval.tick().await;
+        flush(&config).await;
+    }
+}
+
+fn threshold(duration: f32) -> Duration {
+    match duration > 0.0 {
+        true => Duration::from_secs_f32(duration / 2.0).min(MAX_LISTEN),
+        false => MAX_LISTEN,
+    }
+}
+
+fn track_metadata(current: &CurrentTrack) -> Value {
+    let mut info = Map::new();
+    info.insert("submission_client".into(), "bcradio".into());
+    info.insert("submission_client_version".into(), env!("CARGO_PKG_VERSION").into());
+    if current.duration > 0.0 {
+        info.insert("duration_ms".into(), ((current.duration * 1000.0) as u64).into());
+    }
+    if let Some(url) = current.results.item_url() {
+        info.insert("origin_url".into(), url.into());
+        info.insert("music_service".into(), "bandcamp.com".into());
+    }
+
+    let mut m = Map::new();
+    m.insert("artist_name".into(), current.artist_name.to_owned().into());
+    m.insert("track_name".into(), current.track.to_owned().into());
+    if !current.album_title.is_empty() {
+        m.insert("release_name".into(), current.album_title.to_owned().into());
+    }
+    m.insert("additional_info".into(), info.into());
+    m.into()
+}
+
+async fn submit(config: &ListenBrainzConfig, listen_type: &str, listens: &[Value]) -> Result<()> {
+    let url = format!("{}{}", config.url.trim_end_matches('/'), SUBMIT_PATH);
+    let token = format!("Token {}", config.token.as_deref().unwrap_or_default());
+    let body = json!({ "listen_type": listen_type, "payload": listens });
+    post_json(&url, &token, &body).await
+}
+
+/// submit the queued listens, those that still fail stay queued
+async fn flush(config: &ListenBrainzConfig) {
+    let _guard = QUEUE.lock().await;
+    let listens = match load_queue() {
+        Ok(a) if !a.is_empty() => a,
+        Ok(_) => return,
+        Err(e) => {
+            warn!("listenbrainz: {}\r", e);
+            return;
+        }
+    };
+
+    let mut left = listens.as_slice();
+    while !left.is_empty() {
+        let n = left.len().min(BATCH);
+        match submit(config, "import", &left[..n]).await {
+            Ok(()) => info!("listenbrainz: {} queued listens submitted\r", n),
+            Err(e) if is_rejected(&e) => warn!("listenbrainz: {} listens dropped, {}\r", n, e),
+            Err(e) => {
+                warn!("listenbrainz: {}\r", e);
+                break;
+            }
+        }
+        left = &left[n..];
+    }
+    if let Err(e) = save_queue(left) {
+        warn!("listenbrainz: {}\r", e);
+    }
+}
+
+/// the server won't ever take them
+fn is_rejected(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<reqwest::Error>()
+        .and_then(|e| e.status())
+        .is_some_and(|s| s == StatusCode::BAD_REQUEST)
+}
+
+fn queue_path() -> PathBuf {
+    data_dir().join("listenbrainz.jsonl")
+}
+
+fn enqueue(listen: &Value) -> Result<()> {
+    let path = queue_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut f = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(f, "{}", listen)?;
+    Ok(())
+}
+
+fn load_queue() -> Result<Vec<Value>> {
+    let path = queue_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let f = fs::File::open(path)?;
+    Ok(BufReader::new(f)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|l| serde_json::from_str(&l).ok())
+        .collect())
+}
+
+fn save_queue(listens: &[Value]) -> Result<()> {
+    let path = queue_path();
+    if listens.is_empty() {
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        return Ok(());
+    }
+    let body = listens.iter().map(|x| format!("{}\n", x)).collect::<String>();
+    fs::write(path, body)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::submit;
+    use crate::libbc::config::{init_config, Config, ListenBrainzConfig};
+
+    /// against a local stand-in server
+    #[test]
+    fn test_submit() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            // the http client reads the defaults, not the user's config or argv
+            init_config(Config::default());
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let config = ListenBrainzConfig {
+                url: format!("http://{}/", listener.local_addr().unwrap()),
+                token: Some("abc".to_string()),
+            };
+            let server = tokio::spawn(async move {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut req = Vec::new();
+                let mut buf = [0u8; 4096];
+                // the body ends the request
+                while !String::from_utf8_lossy(&req).ends_with('}') {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    req.extend_from_slice(&buf[..n]);
+                }
+                let res = "HTTP/1.1 200 OK\r\ncontent-length: 15\r\n\r\n{\"status\":\"ok\"}";
+                stream.write_all(res.as_bytes()).await.unwrap();
+                String::from_utf8(req).unwrap()
+            });
+
+            let listen = json!({"track_metadata": {"artist_name": "a", "track_name": "b"}});
+            submit(&config, "playing_now", &[listen]).await.unwrap();
+            let req = server.await.unwrap();
+            assert!(req.starts_with("POST /1/submit-listens "));
+            assert!(req.contains("authorization: Token abc"));
+            assert!(req.contains(r#""listen_type":"playing_now""#));
+        });
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Error, Result};
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey};
+use symphonia::core::probe::{Hint, ProbeResult};
+use url::Url;
+
+use crate::models::bc_error::BcradioError;
+use crate::models::shared_data_models::Track;
+
+const EXTENSIONS: [&str; 8] = ["mp3", "flac", "ogg", "oga", "wav", "m4a", "mp4", "aac"];
+
+pub fn is_local(url: &str) -> bool {
+    url.starts_with("file:")
+}
+
+pub fn to_path(url: &str) -> Result<PathBuf> {
+    Url::parse(url)
+        .ok()
+        .and_then(|u| u.to_file_path().ok())
+        .ok_or(Error::from(BcradioError::InvalidUrl))
+}
+
+pub fn read(url: &str) -> Result<Vec<u8>> {
+    Ok(fs::read(to_path(url)?)?)
+}
+
+/// audio files under `path`, in directory order
+pub fn scan(path: &Path) -> Result<VecDeque<Track>> {
+    let mut files = Vec::new();
+    collect(&fs::canonicalize(path)?, &mut files)?;
+    files.sort();
+    Ok(files.iter().filter_map(|x| track(x).ok()).collect())
+}
+
+fn collect(path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    if path.is_dir() {
+        for entry in fs::read_dir(path)? {
+            collect(&entry?.path(), files)?;
+        }
+    } else if path
+        .extension()
+        .and_then(|x| x.to_str())
+        .is_some_and(|x| EXTENSIONS.contains(&x.to_lowercase().as_str()))
+    {
+        files.push(path.to_owned());
+    }
+    Ok(())
+}
+
+fn probe(path: &Path) -> Result<ProbeResult> {
+    let mss = MediaSourceStream::new(Box::new(File::open(path)?), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|x| x.to_str()) {
+        hint.with_extension(ext);
+    }
+    Ok(symphonia::default::get_probe().format(
+        &hint,
+        mss,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?)
+}
+
+/// metadata found outside of the container (id3v2) wins over the container tags
+fn revision(probed: &mut ProbeResult) -> Option<MetadataRevision> {
+    if let Some(rev) = probed.metadata.get().and_then(|m| m.current().cloned()) {
+        return Some(rev);
+    }
+    probed.format.metadata().current().cloned()
+}
+
+pub fn track(path: &Path) -> Result<Track> {
+    let mut probed = probe(path)?;
+
+    let duration = probed
+        .format
+        .default_track()
+        .and_then(|t| Some(t.codec_params.time_base?.calc_time(t.codec_params.n_frames?)))
+        .map(|t| t.seconds as f32 + t.frac as f32)
+        .unwrap_or_default();
+
+    let mut t = Track {
+        track: path
+            .file_stem()
+            .map(|x| x.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        url: Url::from_file_path(path)
+            .map_err(|_| Error::from(BcradioError::InvalidUrl))?
+            .to_string(),
+        duration,
+        ..Default::default()
+    };
+
+    if let Some(rev) = revision(&mut probed) {
+        for tag in rev.tags() {
+            let value = tag.value.to_string();
+            match tag.std_key {
+                Some(StandardTagKey::TrackTitle) => t.track = value,
+                Some(StandardTagKey::Artist) => t.artist_name = value,
+                Some(StandardTagKey::AlbumArtist) if t.artist_name.is_empty() => {
+                    t.artist_name = value
+                }
+                Some(StandardTagKey::Album) => t.album_title = value,
+                Some(StandardTagKey::Genre) => t.genre = Some(value),
+                _ => {}
+            }
+        }
+    }
+    Ok(t)
+}
+
+/// album art embedded in the file
+pub fn embedded_art(url: &str) -> Option<Vec<u8>> {
+    let mut probed = probe(&to_path(url).ok()?).ok()?;
+    revision(&mut probed)?
+        .visuals()
+        .first()
+        .map(|v| v.data.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    /// 1 second of 8kHz mono silence
+    fn wav() -> Vec<u8> {
+        let data = vec![0u8; 16000];
+        let mut v = Vec::new();
+        v.extend_from_slice(b"RIFF");
+        v.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+        v.extend_from_slice(b"WAVEfmt ");
+        v.extend_from_slice(&16u32.to_le_bytes());
+        v.extend_from_slice(&1u16.to_le_bytes());
+        v.extend_from_slice(&1u16.to_le_bytes());
+        v.extend_from_slice(&8000u32.to_le_bytes());
+        v.extend_from_slice(&16000u32.to_le_bytes());
+        v.extend_from_slice(&2u16.to_le_bytes());
+        v.extend_from_slice(&16u16.to_le_bytes());
+        v.extend_from_slice(b"data");
+        v.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        v.extend_from_slice(&data);
+        v
+    }
+
+    #[test]
+    fn test_scan() {
+        let dir = std::env::temp_dir().join("bcradio_test_scan");
+        fs::create_dir_all(dir.join("album")).unwrap();
+        fs::write(dir.join("album").join("01 silence.wav"), wav()).unwrap();
+        fs::write(dir.join("cover.jpg"), b"").unwrap();
+
+        let tracks = super::scan(&dir).unwrap();
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].track, "01 silence");
+        assert_eq!(tracks[0].duration, 1.0);
+        assert!(super::is_local(&tracks[0].url));
+        assert_eq!(super::read(&tracks[0].url).unwrap().len(), 16044);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
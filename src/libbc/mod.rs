@@ -1,12 +1,34 @@
+pub mod album;
 pub mod args;
+pub mod bandcamp_api;
+pub mod cache;
+pub mod command;
+pub mod config;
+#[cfg(unix)]
+pub mod control;
+pub mod deck;
+pub mod export;
+pub mod favorites;
+pub mod filter;
+pub mod history;
+pub mod hooks;
 pub mod http_adapter;
 pub mod http_client;
+pub mod import;
+pub mod listenbrainz;
+pub mod local;
+pub mod mode;
+pub mod mpd;
+#[cfg(all(feature = "mpris", target_os = "linux"))]
+pub mod mpris;
 mod macros;
 pub mod player;
 pub mod playlist;
+pub mod prefetch;
 pub mod progress_bar;
 pub mod scorer;
 pub mod search;
 pub mod shared_data;
 pub mod sink;
+pub mod stream;
 pub mod terminal;
@@ -0,0 +1,85 @@
+use crate::libbc::progress_bar::{println, set_progress_bar_prefix};
+use crate::libbc::shared_data::SharedState;
+use crate::models::shared_data_models::{PlayMode, Repeat};
+
+pub trait Mode {
+    /// shuffle the rest of the queue, tracks added later are shuffled too
+    fn toggle_shuffle(&self);
+    /// off, repeat the current track, repeat the queue
+    fn cycle_repeat(&self);
+    /// pause once the current track has ended
+    fn toggle_stop_after(&self);
+    fn set_repeat(&self, repeat: Repeat);
+}
+
+impl Mode for SharedState {
+    fn toggle_shuffle(&self) {
+        let mode = self.update_mode(|m| m.shuffle = !m.shuffle);
+        if mode.shuffle {
+            self.shuffle_tracklist();
+        }
+        show(&mode);
+    }
+
+    fn cycle_repeat(&self) {
+        self.set_repeat(match self.get_mode().repeat {
+            Repeat::Off => Repeat::One,
+            Repeat::One => Repeat::Queue,
+            Repeat::Queue => Repeat::Off,
+        });
+    }
+
+    fn toggle_stop_after(&self) {
+        let mode = self.update_mode(|m| m.stop_after = !m.stop_after);
+        show(&mode);
+    }
+
+    fn set_repeat(&self, repeat: Repeat) {
+        let mode = self.update_mode(|m| m.repeat = repeat);
+        show(&mode);
+    }
+}
+
+fn show(mode: &PlayMode) {
+    let label = label(mode);
+    match label.is_empty() {
+        true => println("mode: normal"),
+        false => println(&format!("mode: {}", label)),
+    }
+    set_progress_bar_prefix(&label);
+}
+
+/// shown next to the progress bar, empty in normal mode
+pub fn label(mode: &PlayMode) -> String {
+    let repeat = match mode.repeat {
+        Repeat::Off => None,
+        Repeat::One => Some("repeat:one"),
+        Repeat::Queue => Some("repeat:all"),
+    };
+    [
+        mode.shuffle.then_some("shuffle"),
+        repeat,
+        mode.stop_after.then_some("stop-after"),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::label;
+    use crate::models::shared_data_models::{PlayMode, Repeat};
+
+    #[test]
+    fn test_label() {
+        assert_eq!(label(&PlayMode::default()), "");
+        let mode = PlayMode {
+            shuffle: true,
+            repeat: Repeat::Queue,
+            stop_after: true,
+        };
+        assert_eq!(label(&mode), "shuffle repeat:all stop-after");
+    }
+}
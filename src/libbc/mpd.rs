@@ -0,0 +1,566 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fmt::Write as _;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use tokio::io::{self, AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::tcp::OwnedReadHalf;
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::libbc::args::args_mpd;
+use crate::libbc::cache;
+use crate::libbc::command::Command;
+use crate::libbc::import::Import;
+use crate::libbc::player::{PROG, RXTX, VOLUME};
+use crate::libbc::progress_bar::get_progress_bar_position;
+use crate::libbc::shared_data::SharedState;
+use crate::models::shared_data_models::{Repeat, Track};
+
+const GREETING: &str = "OK MPD 0.23.5\n";
+/// how often the player state is compared while a client idles
+const POLL: Duration = Duration::from_millis(500);
+
+/// MPD ACK error codes
+const ACK_ARG: u8 = 2;
+const ACK_NO_EXIST: u8 = 50;
+const ACK_UNKNOWN: u8 = 5;
+
+/// MPD commands answered with an empty list, clients ask for them on connect
+const EMPTY: [&str; 7] = [
+    "ping",
+    "clearerror",
+    "listplaylists",
+    "outputs",
+    "decoders",
+    "tagtypes",
+    "urlhandlers",
+];
+
+/// answered by `commands`, with the empty ones
+const COMMANDS: [&str; 20] = [
+    "add",
+    "addid",
+    "close",
+    "commands",
+    "currentsong",
+    "idle",
+    "next",
+    "noidle",
+    "pause",
+    "ping",
+    "play",
+    "playid",
+    "playlist",
+    "playlistinfo",
+    "plchanges",
+    "previous",
+    "seekcur",
+    "setvol",
+    "status",
+    "stop",
+];
+
+struct Ack(u8, String);
+
+type Reply = Result<String, Ack>;
+
+/// accept MPD clients on the configured address
+pub async fn serve(state: SharedState) {
+    let Some(addr) = args_mpd() else {
+        return;
+    };
+    let listener = match TcpListener::bind(&addr).await {
+        Ok(a) => a,
+        Err(e) => {
+            warn!("mpd: {} {}\r", addr, e);
+            return;
+        }
+    };
+    info!("mpd: listening on {}\r", addr);
+
+    while let Ok((stream, _)) = listener.accept().await {
+        tokio::spawn(session(state.clone(), stream));
+    }
+}
+
+async fn session(state: SharedState, stream: TcpStream) -> io::Result<()> {
+    let (r, mut w) = stream.into_split();
+    let mut lines = BufReader::new(r).lines();
+    w.write_all(GREETING.as_bytes()).await?;
+
+    // idle reports what changed since the client last heard about it
+    let mut last = Snapshot::new(&state);
+    // (list_OK after each command, queued commands)
+    let mut list: Option<(bool, Vec<Vec<String>>)> = None;
+    while let Some(line) = lines.next_line().await? {
+        let args = parse_line(&line);
+        let Some(cmd) = args.first().map(String::as_str) else {
+            continue;
+        };
+        let out = match (cmd, list.as_mut()) {
+            ("command_list_begin", None) => {
+                list = Some((false, vec![]));
+                continue;
+            }
+            ("command_list_ok_begin", None) => {
+                list = Some((true, vec![]));
+                continue;
+            }
+            ("command_list_end", Some(_)) => {
+                let (list_ok, cmds) = list.take().unwrap_or_default();
+                run_list(&state, cmds, list_ok).await
+            }
+            (_, Some((_, cmds))) => {
+                cmds.push(args);
+                continue;
+            }
+            ("close", None) => break,
+            ("idle", None) => match idle(&state, &args[1..], &mut last, &mut lines).await? {
+                Some(out) => out,
+                None => break,
+            },
+            _ => match exec(&state, &args).await {
+                Ok(body) => body + "OK\n",
+                Err(e) => ack(e, 0, cmd),
+            },
+        };
+        w.write_all(out.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+async fn run_list(state: &SharedState, cmds: Vec<Vec<String>>, list_ok: bool) -> String {
+    let mut out = String::new();
+    for (n, args) in cmds.iter().enumerate() {
+        match exec(state, args).await {
+            Ok(body) => out.push_str(&body),
+            Err(e) => return out + &ack(e, n, &args[0]),
+        }
+        if list_ok {
+            out.push_str("list_OK\n");
+        }
+    }
+    out + "OK\n"
+}
+
+fn ack(e: Ack, n: usize, cmd: &str) -> String {
+    format!("ACK [{}@{}] {{{}}} {}\n", e.0, n, cmd, e.1)
+}
+
+/// block until something the client subscribed to has changed, None when it hung up
+async fn idle(
+    state: &SharedState,
+    subsystems: &[String],
+    last: &mut Snapshot,
+    lines: &mut Lines<BufReader<OwnedReadHalf>>,
+) -> io::Result<Option<String>> {
+    let mut tick = tokio::time::interval(POLL);
+    loop {
+        tokio::select! {
+            line = lines.next_line() => {
+                return Ok(match line?.as_deref().map(str::trim) {
+                    Some("noidle") => Some("OK\n".to_string()),
+                    // anything else while idle ends the connection
+                    _ => None,
+                });
+            }
+            _ = tick.tick() => {
+                let now = Snapshot::new(state);
+                let changed = now
+                    .changed(last)
+                    .into_iter()
+                    .filter(|x| subsystems.is_empty() || subsystems.iter().any(|s| s == x))
+                    .map(|x| format!("changed: {}\n", x))
+                    .collect::<String>();
+                if !changed.is_empty() {
+                    *last = now;
+                    return Ok(Some(changed + "OK\n"));
+                }
+            }
+        }
+    }
+}
+
+async fn exec(state: &SharedState, args: &[String]) -> Reply {
+    let arg = |n: usize| args.get(n).map(String::as_str);
+    let number = |n: usize| -> Result<i64, Ack> {
+        arg(n)
+            .and_then(|x| x.parse().ok())
+            .ok_or(Ack(ACK_ARG, "need an integer".to_string()))
+    };
+    match args[0].as_str() {
+        "status" => Ok(status(state)),
+        "currentsong" => Ok(playlist(state)
+            .first()
+            .filter(|_| is_current(state))
+            .map(|t| song(t, 0))
+            .unwrap_or_default()),
+        "playlistinfo" | "playlist" | "plchanges" => Ok(playlist(state)
+            .iter()
+            .enumerate()
+            .map(|(pos, t)| song(t, pos))
+            .collect()),
+        "play" => match arg(1) {
+            None => play(state, true).await,
+            Some(_) => play_pos(state, number(1)? as usize).await,
+        },
+        "playid" => {
+            let id = number(1)? as u32;
+            match playlist(state).iter().position(|t| song_id(t) == id) {
+                Some(pos) => play_pos(state, pos).await,
+                None => Err(Ack(ACK_NO_EXIST, "No such song".to_string())),
+            }
+        }
+        "pause" => match arg(1) {
+            Some("1") => play(state, false).await,
+            Some("0") => play(state, true).await,
+            _ => send(Command::TogglePause).await,
+        },
+        "stop" => play(state, false).await,
+        "next" => send(Command::Next).await,
+        "previous" => send(Command::Previous).await,
+        "setvol" => {
+            let vol = number(1)?.clamp(0, 100);
+            send(Command::Volume(((vol * 9 + 50) / 100) as u8)).await
+        }
+        "seekcur" => {
+            let time = arg(1).unwrap_or_default();
+            let secs = time
+                .parse::<f64>()
+                .map_err(|_| Ack(ACK_ARG, "need a time".to_string()))? as i64;
+            match time.starts_with(['+', '-']) {
+                true => send(Command::Seek(secs as i32)).await,
+                false => send(Command::SeekTo(secs.max(0) as u64)).await,
+            }
+        }
+        "add" => match arg(1) {
+            Some(url) => send(Command::Enqueue(vec![url.to_string()])).await,
+            None => Err(Ack(ACK_ARG, "need a url".to_string())),
+        },
+        "addid" => match arg(1) {
+            Some(url) => add_id(state, url).await,
+            None => Err(Ack(ACK_ARG, "need a url".to_string())),
+        },
+        "commands" => Ok(COMMANDS
+            .iter()
+            .chain(EMPTY.iter())
+            .map(|x| format!("command: {}\n", x))
+            .collect()),
+        x if EMPTY.contains(&x) => Ok(String::new()),
+        x => Err(Ack(ACK_UNKNOWN, format!("unknown command \"{}\"", x))),
+    }
+}
+
+async fn send(cmd: Command) -> Reply {
+    RXTX.deref()
+        .0
+        .send(cmd)
+        .await
+        .map(|_| String::new())
+        .map_err(|e| Ack(ACK_NO_EXIST, e.to_string()))
+}
+
+/// queue `url` here rather than in the player, the reply carries the id of its first track
+async fn add_id(state: &SharedState, url: &str) -> Reply {
+    let before = playlist(state).iter().map(song_id).collect::<Vec<_>>();
+    match state.enqueue(vec![url.to_string()]).await {
+        Ok(n) if n > 0 => {}
+        Ok(_) => return Err(Ack(ACK_NO_EXIST, "No such song".to_string())),
+        Err(e) => return Err(Ack(ACK_NO_EXIST, e.to_string())),
+    }
+    // shuffle may have mixed it into the queue
+    playlist(state)
+        .iter()
+        .map(song_id)
+        .find(|x| !before.contains(x))
+        .map(|id| format!("Id: {}\n", id))
+        .ok_or(Ack(ACK_NO_EXIST, "No such song".to_string()))
+}
+
+/// play or pause, nothing to do when it already is
+async fn play(state: &SharedState, play: bool) -> Reply {
+    match is_current(state) && *PROG.lock().unwrap() != play {
+        true => send(Command::TogglePause).await,
+        false => Ok(String::new()),
+    }
+}
+
+/// the current track is at 0 while something plays, the queue follows
+async fn play_pos(state: &SharedState, pos: usize) -> Reply {
+    let offset = is_current(state) as usize;
+    if pos < offset {
+        return play(state, true).await;
+    }
+    if pos - offset >= state.peek_tracks(usize::MAX).len() {
+        return Err(Ack(ACK_ARG, "Bad song index".to_string()));
+    }
+    send(Command::PlayQueued(pos - offset)).await
+}
+
+fn is_current(state: &SharedState) -> bool {
+    !state.get_current_track_info().url.is_empty()
+}
+
+/// the current track followed by the queue
+fn playlist(state: &SharedState) -> Vec<Track> {
+    let mut v = Vec::new();
+    if is_current(state) {
+        v.push(state.get_current_track_info().to_track());
+    }
+    v.extend(state.peek_tracks(usize::MAX).into_iter().map(|x| x.0));
+    v
+}
+
+fn status(state: &SharedState) -> String {
+    let mode = state.get_mode();
+    let current = state.get_current_track_info();
+    let playlist = playlist(state);
+    let mut s = String::new();
+    let _ = write!(
+        s,
+        "volume: {}\nrepeat: {}\nrandom: {}\nsingle: {}\nconsume: 0\nplaylist: {}\nplaylistlength: {}\nstate: {}\n",
+        (*VOLUME.lock().unwrap() as u32 * 100 + 4) / 9,
+        (mode.repeat != Repeat::Off) as u8,
+        mode.shuffle as u8,
+        (mode.repeat == Repeat::One || mode.stop_after) as u8,
+        version(&playlist),
+        playlist.len(),
+        play_state(state),
+    );
+    if is_current(state) {
+        let elapsed = get_progress_bar_position();
+        let _ = write!(
+            s,
+            "song: 0\nsongid: {}\ntime: {}:{}\nelapsed: {}.000\nduration: {:.3}\n",
+            song_id(&playlist[0]),
+            elapsed,
+            current.duration.round() as u64,
+            elapsed,
+            current.duration,
+        );
+        if let Some(next) = playlist.get(1) {
+            let _ = write!(s, "nextsong: 1\nnextsongid: {}\n", song_id(next));
+        }
+    }
+    s
+}
+
+fn play_state(state: &SharedState) -> &'static str {
+    if !is_current(state) {
+        "stop"
+    } else if *PROG.lock().unwrap() {
+        "play"
+    } else {
+        "pause"
+    }
+}
+
+fn song(t: &Track, pos: usize) -> String {
+    // the stream url expires, the page can be added again
+    let mut s = format!(
+        "file: {}\nTitle: {}\nArtist: {}\nAlbum: {}\n",
+        t.results.item_url().unwrap_or_else(|| t.url.to_owned()),
+        t.track,
+        t.artist_name,
+        t.album_title
+    );
+    if let Some(genre) = &t.genre {
+        let _ = writeln!(s, "Genre: {}", genre);
+    }
+    let _ = write!(
+        s,
+        "Time: {}\nduration: {:.3}\nPos: {}\nId: {}\n",
+        t.duration.round() as u64,
+        t.duration,
+        pos,
+        song_id(t)
+    );
+    s
+}
+
+/// stable for the track within a run, also when its url is refreshed
+fn song_id(t: &Track) -> u32 {
+    let mut h = DefaultHasher::new();
+    cache::key(&t.url).hash(&mut h);
+    (h.finish() & 0x7fff_ffff) as u32
+}
+
+/// changes with the playlist, clients only compare it
+fn version(playlist: &[Track]) -> u32 {
+    let mut h = DefaultHasher::new();
+    playlist.iter().for_each(|t| cache::key(&t.url).hash(&mut h));
+    (h.finish() & 0x7fff_ffff) as u32
+}
+
+struct Snapshot {
+    url: String,
+    position: u64,
+    at: Instant,
+    state: &'static str,
+    volume: u8,
+    mode: String,
+    version: u32,
+}
+
+impl Snapshot {
+    fn new(state: &SharedState) -> Snapshot {
+        Snapshot {
+            url: state.get_current_track_info().url,
+            position: get_progress_bar_position(),
+            at: Instant::now(),
+            state: play_state(state),
+            volume: *VOLUME.lock().unwrap(),
+            mode: format!("{:?}", state.get_mode()),
+            version: version(&playlist(state)),
+        }
+    }
+
+    /// idle subsystems
+    fn changed(&self, last: &Snapshot) -> Vec<&'static str> {
+        let mut v = Vec::new();
+        // where playback would be by now, a seek is off by more than a tick
+        let expected = match last.state {
+            "play" => last.position + (self.at - last.at).as_secs(),
+            _ => last.position,
+        };
+        if self.url != last.url || self.state != last.state || self.position.abs_diff(expected) > 2 {
+            v.push("player");
+        }
+        if self.volume != last.volume {
+            v.push("mixer");
+        }
+        if self.mode != last.mode {
+            v.push("options");
+        }
+        if self.version != last.version {
+            v.push("playlist");
+        }
+        v
+    }
+}
+
+/// `add "https://a b"` => [add, https://a b]
+fn parse_line(line: &str) -> Vec<String> {
+    let mut v = Vec::new();
+    let mut chars = line.trim().chars();
+    let mut cur = String::new();
+    let mut quoted = false;
+    let mut token = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                token = true;
+            }
+            '\\' if quoted => cur.extend(chars.next()),
+            c if c.is_whitespace() && !quoted => {
+                if token {
+                    v.push(std::mem::take(&mut cur));
+                    token = false;
+                }
+            }
+            c => {
+                cur.push(c);
+                token = true;
+            }
+        }
+    }
+    if token {
+        v.push(cur);
+    }
+    v
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Deref;
+
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{TcpListener, TcpStream};
+
+    use super::{parse_line, session, song, GREETING};
+    use crate::libbc::command::Command;
+    use crate::libbc::config::{init_config, Config};
+    use crate::libbc::player::RXTX;
+    use crate::libbc::shared_data::SharedState;
+    use crate::models::shared_data_models::Track;
+
+    #[test]
+    fn test_parse_line() {
+        assert_eq!(parse_line("status"), ["status"]);
+        assert_eq!(parse_line("setvol  50 "), ["setvol", "50"]);
+        assert_eq!(
+            parse_line(r#"add "https://a.bandcamp.com/track/b \"c\"""#),
+            ["add", r#"https://a.bandcamp.com/track/b "c""#]
+        );
+        assert_eq!(parse_line(r#"find "" x"#), ["find", "", "x"]);
+
+        let t = Track {
+            url: "u".to_string(),
+            track: "t".to_string(),
+            duration: 61.5,
+            ..Default::default()
+        };
+        assert!(song(&t, 3).contains("Time: 62\nduration: 61.500\nPos: 3\n"));
+    }
+
+    /// a client against a session over a local connection
+    #[test]
+    fn test_session() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            init_config(Config::default());
+            let state = SharedState::default();
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let server = state.clone();
+            tokio::spawn(async move {
+                let (stream, _) = listener.accept().await.unwrap();
+                session(server, stream).await
+            });
+
+            let (r, mut w) = TcpStream::connect(addr).await.unwrap().into_split();
+            let mut lines = BufReader::new(r).lines();
+            // the lines up to the next OK
+            macro_rules! reply {
+                () => {{
+                    let mut v = Vec::new();
+                    while let Some(line) = lines.next_line().await.unwrap() {
+                        if line == "OK" {
+                            break;
+                        }
+                        v.push(line);
+                    }
+                    v
+                }};
+                ($cmd:expr) => {{
+                    w.write_all(format!("{}\n", $cmd).as_bytes()).await.unwrap();
+                    reply!()
+                }};
+            }
+            assert_eq!(lines.next_line().await.unwrap().as_deref(), Some(GREETING.trim()));
+
+            let status = reply!("status");
+            assert!(status.contains(&"state: stop".to_string()));
+            assert!(status.contains(&"playlistlength: 0".to_string()));
+
+            assert!(reply!(r#"add "https://a.bandcamp.com/track/b""#).is_empty());
+            assert_eq!(
+                RXTX.deref().1.try_recv().ok(),
+                Some(Command::Enqueue(vec!["https://a.bandcamp.com/track/b".to_string()]))
+            );
+
+            w.write_all(b"idle player\n").await.unwrap();
+            state.move_to_current_track(&Track {
+                url: "https://t4.bcbits.com/stream/x/mp3-128/1".to_string(),
+                ..Default::default()
+            });
+            assert_eq!(reply!(), ["changed: player"]);
+        });
+    }
+}
@@ -0,0 +1,385 @@
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::time::Duration;
+
+use log::{info, warn};
+use zbus::fdo;
+use zbus::object_server::SignalContext;
+use zbus::zvariant::{ObjectPath, OwnedValue, Value};
+use zbus::{connection, interface};
+
+use crate::libbc::args::args_art_url;
+use crate::libbc::command::Command;
+use crate::libbc::local;
+use crate::libbc::mode::Mode;
+use crate::libbc::player::{PROG, RXTX, VOLUME};
+use crate::libbc::progress_bar::get_progress_bar_position;
+use crate::libbc::shared_data::SharedState;
+use crate::models::shared_data_models::{CurrentTrack, Repeat};
+
+const BUS_NAME: &str = "org.mpris.MediaPlayer2.bcradio";
+const PATH: &str = "/org/mpris/MediaPlayer2";
+const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+/// how often the player state is compared for change signals
+const POLL: Duration = Duration::from_millis(500);
+
+/// register on the session bus and signal changes of the player state
+pub async fn serve(state: SharedState) {
+    if let Err(e) = run(state).await {
+        warn!("mpris: {}\r", e);
+    }
+}
+
+async fn run(state: SharedState) -> zbus::Result<()> {
+    let conn = connection::Builder::session()?
+        .name(BUS_NAME)?
+        .serve_at(PATH, Root)?
+        .serve_at(PATH, Player { state: state.clone() })?
+        .build()
+        .await?;
+    info!("mpris: registered as {}\r", BUS_NAME);
+
+    let iface = conn.object_server().interface::<_, Player>(PATH).await?;
+    let mut last = Snapshot::new(&state);
+    let mut interval = tokio::time::interval(POLL);
+    loop {
+        interval.tick().await;
+        let now = Snapshot::new(&state);
+        let ctx = iface.signal_context();
+        let player = iface.get().await;
+        if now.url != last.url {
+            player.metadata_changed(ctx).await?;
+        } else if now.position < last.position || now.position > last.position + 2 {
+            // a jump the clock can't explain
+            Player::seeked(ctx, micros(now.position)).await?;
+        }
+        if now.status != last.status {
+            player.playback_status_changed(ctx).await?;
+        }
+        if now.volume != last.volume {
+            player.volume_changed(ctx).await?;
+        }
+        if now.shuffle != last.shuffle {
+            player.shuffle_changed(ctx).await?;
+        }
+        if now.repeat != last.repeat {
+            player.loop_status_changed(ctx).await?;
+        }
+        last = now;
+    }
+}
+
+struct Snapshot {
+    url: String,
+    position: u64,
+    status: &'static str,
+    volume: u8,
+    shuffle: bool,
+    repeat: Repeat,
+}
+
+impl Snapshot {
+    fn new(state: &SharedState) -> Snapshot {
+        let mode = state.get_mode();
+        Snapshot {
+            url: state.get_current_track_info().url,
+            position: get_progress_bar_position(),
+            status: status(state),
+            volume: *VOLUME.lock().unwrap(),
+            shuffle: mode.shuffle,
+            repeat: mode.repeat,
+        }
+    }
+}
+
+fn status(state: &SharedState) -> &'static str {
+    if state.get_current_track_info().url.is_empty() {
+        "Stopped"
+    } else if *PROG.lock().unwrap() {
+        "Playing"
+    } else {
+        "Paused"
+    }
+}
+
+fn micros(secs: u64) -> i64 {
+    secs as i64 * 1_000_000
+}
+
+async fn send(cmd: Command) -> fdo::Result<()> {
+    RXTX.deref()
+        .0
+        .send(cmd)
+        .await
+        .map_err(|e| fdo::Error::Failed(e.to_string()))
+}
+
+/// unique per play
+fn track_id(current: &CurrentTrack) -> String {
+    match current.url.is_empty() {
+        true => NO_TRACK.to_string(),
+        false => format!("/org/bcradio/track/{}", current.play_date.timestamp_millis()),
+    }
+}
+
+/// xesam metadata of the current track
+fn metadata(current: &CurrentTrack) -> HashMap<String, OwnedValue> {
+    let mut m = HashMap::new();
+    let mut insert = |k: &str, v: Value| {
+        if let Ok(v) = OwnedValue::try_from(v) {
+            m.insert(k.to_string(), v);
+        }
+    };
+    if let Ok(id) = ObjectPath::try_from(track_id(current)) {
+        insert("mpris:trackid", id.into());
+    }
+    if current.url.is_empty() {
+        return m;
+    }
+    if current.duration > 0.0 {
+        insert("mpris:length", ((current.duration * 1e6) as i64).into());
+    }
+    if let Some(art_id) = current.art_id {
+        insert("mpris:artUrl", format!("{}/a{}_16.jpg", args_art_url(), art_id).into());
+    }
+    insert("xesam:title", current.track.to_owned().into());
+    insert("xesam:artist", vec![current.artist_name.to_owned()].into());
+    insert("xesam:album", current.album_title.to_owned().into());
+    let url = match local::is_local(&current.url) {
+        true => Some(current.url.to_owned()),
+        false => current.results.item_url(),
+    };
+    if let Some(url) = url {
+        insert("xesam:url", url.into());
+    }
+    if let Some(genre) = &current.genre {
+        insert("xesam:genre", vec![genre.to_owned()].into());
+    }
+    m
+}
+
+struct Root;
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+    fn raise(&self) {}
+
+    fn quit(&self) {}
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> &str {
+        "bcradio"
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        vec!["https".to_string(), "file".to_string()]
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        vec!["audio/mpeg".to_string()]
+    }
+}
+
+struct Player {
+    state: SharedState,
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    async fn next(&self) -> fdo::Result<()> {
+        send(Command::Next).await
+    }
+
+    async fn previous(&self) -> fdo::Result<()> {
+        send(Command::Previous).await
+    }
+
+    async fn pause(&self) -> fdo::Result<()> {
+        match status(&self.state) {
+            "Playing" => send(Command::TogglePause).await,
+            _ => Ok(()),
+        }
+    }
+
+    async fn play(&self) -> fdo::Result<()> {
+        match status(&self.state) {
+            "Paused" => send(Command::TogglePause).await,
+            _ => Ok(()),
+        }
+    }
+
+    async fn play_pause(&self) -> fdo::Result<()> {
+        send(Command::TogglePause).await
+    }
+
+    /// there is no stopped state, pause
+    async fn stop(&self) -> fdo::Result<()> {
+        self.pause().await
+    }
+
+    async fn seek(&self, offset: i64) -> fdo::Result<()> {
+        send(Command::Seek((offset / 1_000_000) as i32)).await
+    }
+
+    async fn set_position(&self, track_id: ObjectPath<'_>, position: i64) -> fdo::Result<()> {
+        if track_id.as_str() != self::track_id(&self.state.get_current_track_info()) || position < 0 {
+            // stale request
+            return Ok(());
+        }
+        send(Command::SeekTo((position / 1_000_000) as u64)).await
+    }
+
+    async fn open_uri(&self, uri: String) -> fdo::Result<()> {
+        send(Command::Enqueue(vec![uri])).await
+    }
+
+    #[zbus(signal)]
+    async fn seeked(ctx: &SignalContext<'_>, position: i64) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn playback_status(&self) -> &str {
+        status(&self.state)
+    }
+
+    #[zbus(property)]
+    fn loop_status(&self) -> &str {
+        match self.state.get_mode().repeat {
+            Repeat::Off => "None",
+            Repeat::One => "Track",
+            Repeat::Queue => "Playlist",
+        }
+    }
+
+    #[zbus(property)]
+    fn set_loop_status(&mut self, value: String) {
+        let repeat = match value.as_str() {
+            "Track" => Repeat::One,
+            "Playlist" => Repeat::Queue,
+            _ => Repeat::Off,
+        };
+        self.state.set_repeat(repeat);
+    }
+
+    #[zbus(property)]
+    fn shuffle(&self) -> bool {
+        self.state.get_mode().shuffle
+    }
+
+    #[zbus(property)]
+    fn set_shuffle(&mut self, value: bool) {
+        if value != self.state.get_mode().shuffle {
+            self.state.toggle_shuffle();
+        }
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        metadata(&self.state.get_current_track_info())
+    }
+
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        *VOLUME.lock().unwrap() as f64 / 9.0
+    }
+
+    #[zbus(property)]
+    async fn set_volume(&mut self, value: f64) {
+        let _ = send(Command::Volume((value.clamp(0.0, 1.0) * 9.0).round() as u8)).await;
+    }
+
+    #[zbus(property)]
+    fn position(&self) -> i64 {
+        micros(get_progress_bar_position())
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zbus::zvariant::{OwnedValue, Value};
+
+    use super::metadata;
+    use crate::models::shared_data_models::CurrentTrack;
+
+    #[test]
+    fn test_metadata() {
+        let value = |v: Value| OwnedValue::try_from(v).unwrap();
+        let m = metadata(&CurrentTrack::default());
+        assert_eq!(m.len(), 1);
+
+        let current = CurrentTrack {
+            url: "file:///music/a.mp3".to_string(),
+            track: "a".to_string(),
+            artist_name: "b".to_string(),
+            duration: 1.5,
+            ..Default::default()
+        };
+        let m = metadata(&current);
+        assert_eq!(m["xesam:title"], value("a".into()));
+        assert_eq!(m["xesam:artist"], value(vec!["b".to_string()].into()));
+        assert_eq!(m["mpris:length"], value(1_500_000i64.into()));
+        assert_eq!(m["xesam:url"], value("file:///music/a.mp3".into()));
+        assert!(!m.contains_key("mpris:artUrl"));
+    }
+}
@@ -1,5 +1,6 @@
 use std::env;
 use std::ops::Deref;
+use std::path::Path;
 use std::sync::{LazyLock, Mutex};
 use std::time::Duration;
 
@@ -8,26 +9,50 @@ use async_channel::{unbounded, Receiver, Sender};
 use async_trait::async_trait;
 use futures::future::abortable;
 
-use rodio::Sink;
-use crate::libbc::progress_bar::{disable_tick, disable_tick_on_screen, enable_tick, enable_tick_on_screen, get_progress_bar_current_position, run, update_song_info_on_screen};
-use crate::libbc::args::{about, args_genre, args_list_devices, args_sub_genre, args_verbose_log};
-use crate::libbc::http_client::{get_blocking_request};
+use crate::libbc::progress_bar::{disable_spinner, disable_tick, enable_spinner, disable_tick_on_screen, enable_tick, enable_tick_on_screen, get_progress_bar_current_position, get_progress_bar_position, println, set_progress_bar_position, set_progress_bar_prefix, run, update_song_info_on_screen};
+use crate::libbc::album::Album;
+use crate::libbc::args::{args_category, args_crossfade, args_genre, args_import, args_list_devices, args_local, args_location, args_slice, args_streaming, args_sub_genre, args_verbose_log};
+use crate::libbc::bandcamp_api::api;
+use crate::libbc::deck::{BoxSource, Deck};
+use crate::libbc::cache::{self, Cache};
+use crate::libbc::command::{keymap, Command};
+#[cfg(unix)]
+use crate::libbc::control;
+#[cfg(all(feature = "mpris", target_os = "linux"))]
+use crate::libbc::mpris;
+use crate::libbc::export::Export;
+use crate::libbc::favorites::Favorites;
+use crate::libbc::history::History;
+use crate::libbc::hooks::{self, Hook};
+use crate::libbc::import::Import;
+use crate::libbc::listenbrainz;
+use crate::libbc::local;
+use crate::libbc::mode::{self, Mode};
+use crate::libbc::mpd;
 use crate::libbc::playlist::{format, PlayList};
+use crate::libbc::prefetch::{self, Prefetch};
 use crate::libbc::search::Search;
 use crate::libbc::shared_data::SharedState;
 use crate::libbc::sink::{list_host_devices, Mp3, MusicStruct};
+use crate::libbc::stream;
 use crate::libbc::terminal::{quit, show_alt_term, show_alt_term2};
-use crate::models::shared_data_models::ResultsJson;
+use crate::models::shared_data_models::{PlayMode, Repeat, ResultsJson, Station, Track};
 use crate::{ceil, format_duration};
 use crate::models::bc_error::BcradioError;
 
+/// seconds before the end of a track when the next one is queued for gapless playback
+const GAPLESS_LEAD: f32 = 5.0;
+/// previous restarts the current track once it has played this long
+const RESTART_AFTER: u64 = 3;
+
 fn map_volume_to_rodio_volume(volume: u8) -> f32 {
     (volume as f32 / 9_f32).powf(2.0)
 }
 
-pub static RXTX: LazyLock<(Sender<char>, Receiver<char>)> = LazyLock::new(unbounded);
+pub static RXTX: LazyLock<(Sender<Command>, Receiver<Command>)> = LazyLock::new(unbounded);
 pub static PARK: LazyLock<Mutex<bool>> = LazyLock::new(|| Mutex::new(false));
 pub static PROG: LazyLock<Mutex<bool>> = LazyLock::new(|| Mutex::new(true));
+pub static VOLUME: LazyLock<Mutex<u8>> = LazyLock::new(|| Mutex::new(9));
 pub fn park_lock() { *PARK.lock().unwrap() = false; }
 pub fn park_unlock() { *PARK.lock().unwrap() = true; }
 
@@ -55,62 +80,148 @@ impl Player<'static> for SharedState {
             quit(Error::from(BcradioError::Quit));
         }
 
-        if args_genre().is_some() || args_sub_genre().is_some() {
-            let post_data = state.silent(args_genre(), args_sub_genre())?;
-            state.store_results(&post_data).await;
+        if let Some(path) = args_local() {
+            state.set_station(Station::Local(path.clone()));
+            if let Err(e) = state.store_results(&Default::default()).await {
+                quit(e);
+            }
+            if state.queue_length_from_truck_list() == 0 {
+                quit(Error::msg(format!("no audio files in {}", path.display())));
+            }
+        } else if let Some(path) = args_import() {
+            state.set_station(Station::Playlist);
+            if let Err(e) = state.import(&path).await {
+                quit(Error::msg(format!("import {}: {}", path.display(), e)));
+            }
+            if state.queue_length_from_truck_list() == 0 {
+                quit(Error::msg(format!("nothing to play in {}", path.display())));
+            }
+        } else if args_genre().is_some()
+            || args_sub_genre().is_some()
+            || args_location().is_some()
+            || args_slice().is_some()
+            || args_category().is_some()
+        {
+            let post_data = state.silent(
+                args_genre(),
+                args_sub_genre(),
+                args_location(),
+                args_slice(),
+                args_category(),
+            )?;
+            if let Err(e) = state.store_results(&post_data).await {
+                quit(e);
+            }
         } else {
             match state.ask() {
-                Ok(post_data) => state.store_results(&post_data).await,
+                Ok(post_data) => {
+                    if let Err(e) = state.store_results(&post_data).await {
+                        quit(e);
+                    }
+                }
                 Err(e) => quit(e),
             };
         }
 
+        #[cfg(unix)]
+        tokio::spawn(control::serve(state.clone()));
+        #[cfg(all(feature = "mpris", target_os = "linux"))]
+        tokio::spawn(mpris::serve(state.clone()));
+        tokio::spawn(mpd::serve(state.clone()));
+        tokio::spawn(listenbrainz::serve());
+
         park_unlock();
-        let mut _current_volume = 9;
 
         let stream_handle = MusicStruct::new();
-        let sink = Sink::try_new(&stream_handle.stream_handle.unwrap())?;
+        let mut deck = Deck::try_new(&stream_handle.stream_handle.unwrap())?;
 
+        // a download that failed with nothing queued behind it
+        let mut failed = None;
         loop {
-            if sink.empty() {
+            if deck.empty() {
                 state.fill_playlist().await?;
+                if let Some(e) = failed.take() {
+                    if state.queue_length_from_truck_list() == 0 {
+                        quit(Error::msg(e));
+                    }
+                }
             }
 
-            state.enqueue_truck_buffer().await?;
+            // a head that is not downloaded yet gets streamed
+            play(&state, &mut deck).await?;
 
-            play(&state, &sink).await?;
+            state.prefetch()?;
+            listenbrainz::tick();
 
             if let Ok(res) = RXTX.deref().1.try_recv() {
                 match res {
-                    '0'..='9' => {
-                        // change volume
-                        _current_volume = res.to_string().parse()?;
-                        sink.set_volume(map_volume_to_rodio_volume(_current_volume));
+                    Command::Volume(v) => {
+                        *VOLUME.lock().unwrap() = v;
+                        deck.set_volume(map_volume_to_rodio_volume(v));
+                    }
+                    Command::Next => {
+                        state.set_skipped();
+                        deck.skip()
                     }
-                    'n' => sink.stop(),
-                    'p' => {
+                    Command::Seek(secs) => seek_by(&state, &deck, secs as i64),
+                    Command::SeekTo(secs) => seek(&state, &deck, secs),
+                    Command::Jump => jump(&state, &deck)?,
+                    Command::Previous => previous(&state, &mut deck),
+                    Command::Shuffle => state.toggle_shuffle(),
+                    Command::Repeat => state.cycle_repeat(),
+                    Command::StopAfter => state.toggle_stop_after(),
+                    Command::NextSlice => state.next_slice().await?,
+                    Command::ExpandAlbum => state.expand_current().await,
+                    Command::TogglePause => {
                         // play pause
-                        if sink.is_paused() {
-                            sink.play();
+                        let current = state.get_current_track_info().to_track();
+                        if deck.is_paused() {
+                            deck.play();
                             enable_tick();
+                            hooks::run(Hook::Resume, &current, None);
                         } else {
-                            sink.pause();
+                            deck.pause();
                             disable_tick();
+                            hooks::run(Hook::Pause, &current, None);
                         }
                     }
-                    'i' => info(&state)?,
-                    'm' => menu(&state)?,
-                    'l' => {
+                    Command::Info => info(&state)?,
+                    Command::Menu => menu(&state)?,
+                    Command::Playlist => {
                         state.fill_playlist().await?;
                         playlist(&state)?
                     }
-                    'f' => state.search(None).await?,
-                    's' => search(&state).await?,
-                    'h' => help()?,
-                    'Q' => {
+                    Command::ToggleFavorite => state.toggle_favorite()?,
+                    Command::Favorites => favorites(&state)?,
+                    Command::ArtistSearch => state.search(None, true).await?,
+                    Command::Search => search(&state).await?,
+                    Command::Help => help()?,
+                    Command::Export => export(&state)?,
+                    Command::Import => import(&state).await?,
+                    Command::History => history(&state)?,
+                    Command::Enqueue(entries) => {
+                        if let Err(e) = state.enqueue(entries).await {
+                            let current = state.get_current_track_info().to_track();
+                            hooks::run(Hook::Error, &current, Some(&format!("enqueue: {}", e)));
+                            println(&format!("enqueue: {}", e));
+                        }
+                    }
+                    // from the control socket, nobody is at the prompt
+                    Command::SearchFor(query) => {
+                        if let Err(e) = state.search(Some(query), false).await {
+                            println(&format!("search: {}", e));
+                        }
+                    }
+                    Command::Failed(e) => failed = Some(e),
+                    Command::PlayQueued(pos) => {
+                        if state.promote_track(pos) {
+                            state.set_skipped();
+                            deck.stop();
+                        }
+                    }
+                    Command::Quit => {
                         break;
                     }
-                    _ => {}
                 }
             }
             tokio::time::sleep(Duration::from_millis(500)).await;
@@ -127,6 +238,7 @@ impl Player<'static> for SharedState {
             }
         });
         tokio::join!(hdl1).0.is_ok().then(|| hdl0.abort());
+        state.record_history();
         Ok(())
     }
 
@@ -194,6 +306,37 @@ impl Player<'static> for SharedState {
                     " {:>14} {}",
                     "Release Date:", g.release_date
                 )]);
+                let slice = self.next_post().slice;
+                if let Some(slice) = self.get_slices().iter().find(|x| x.slug == slice) {
+                    v.append(&mut vec![format!(
+                        " {:>14} {}",
+                        "Station Sort:", slice.label
+                    )]);
+                }
+                let location = self.get_location();
+                if location.id != 0 {
+                    v.append(&mut vec![format!(
+                        " {:>14} {}",
+                        "Station From:", location.label
+                    )]);
+                }
+                let category = self.get_category();
+                if category.id != 0 {
+                    v.append(&mut vec![format!(
+                        " {:>14} {}",
+                        "Station Media:", category.label
+                    )]);
+                }
+                for (n, p) in g.package_info.iter().flatten().enumerate() {
+                    v.append(&mut vec![format!(
+                        " {:>14} {} / {} {} {:3.2}",
+                        if n == 0 { "Formats:" } else { "" },
+                        p.format,
+                        p.title,
+                        p.price.currency,
+                        p.price.amount as f64 / 100.0
+                    )]);
+                }
                 v.append(&mut vec![format!(
                     " {:>14} {}",
                     "Label URL:",
@@ -218,49 +361,249 @@ impl Player<'static> for SharedState {
                     g.item_url.unwrap_or_default()
                 )]);
             }
-            ResultsJson::None => {}
+            ResultsJson::None => {
+                if local::is_local(&current_track.url) {
+                    v.append(&mut vec![format!(
+                        " {:>14} {}",
+                        "Genre:",
+                        current_track.genre.clone().unwrap_or_default()
+                    )]);
+                    v.append(&mut vec![format!(" {:>14} {}", "File:", current_track.url)]);
+                }
+            }
         }
 
         Ok(v)
     }
 }
 
-async fn play(state: &SharedState, sink: &Sink) -> Result<()> {
-    if sink.empty() && state.get_buffer_set_queue_length() > 0 {
-        let buf = state.get_track_buffer(0);
-        if buf.is_empty() {
+async fn play(state: &SharedState, deck: &mut Deck) -> Result<()> {
+    if let Some(track) = deck.advanced() {
+        start(state, &track)?;
+    }
+    let mode = state.get_mode();
+    if deck.ended() && !state.is_skipped() {
+        ended(state, deck, mode);
+    }
+    let Some((track, len)) = state.peek_tracks(1).pop() else {
+        return Ok(());
+    };
+    if !is_ready(&track, len) {
+        // the prefetch worker has it
+        return Ok(());
+    }
+    let track = match len {
+        0 => state.refresh(track).await,
+        _ => track,
+    };
+
+    if deck.empty() {
+        if deck.is_held() {
             return Ok(());
         }
+        let buf = state.get_track_buffer(0);
+        // a track that can't be played never becomes current
+        if let Some(source) = source(state, &track, buf).await? {
+            start(state, &track)?;
+            deck.play_now(source);
+        }
+        return Ok(());
+    }
+
+    // queue the next track ahead of the end of the current one
+    let fade = args_crossfade(&state.get_station(), &state.get_genre());
+    let lead = match fade.is_zero() {
+        true => GAPLESS_LEAD,
+        false => fade.as_secs_f32(),
+    };
+    let current = state.get_current_track_info();
+    let remaining = current.duration - get_progress_bar_position() as f32;
+    // these modes act on the end of the track
+    if mode.repeat == Repeat::One || mode.stop_after {
+        return Ok(());
+    }
+    if deck.has_pending() || current.duration <= 0.0 || remaining > lead {
+        return Ok(());
+    }
+    if let Some(source) = source(state, &track, state.get_track_buffer(0)).await? {
+        if let Some(track) = deck.queue_next(track, source, fade) {
+            start(state, &track)?;
+        }
+    }
+    Ok(())
+}
 
-        state.move_to_current_track();
+/// the current track has played to its end
+fn ended(state: &SharedState, deck: &mut Deck, mode: PlayMode) {
+    if mode.repeat == Repeat::One {
+        state.rewind(false);
+    }
+    if mode.stop_after {
+        let mode = state.update_mode(|m| m.stop_after = false);
+        set_progress_bar_prefix(&mode::label(&mode));
+        deck.hold();
+        disable_tick();
+        hooks::run(Hook::Pause, &state.get_current_track_info().to_track(), None);
+        println("stopped, p to resume");
+    }
+}
 
-        update_song_info_on_screen(&state.get_current_track_info())?;
+/// downloaded, or can be streamed. a prefetch that is running gets to finish,
+/// streaming it as well would download it twice
+fn is_ready(track: &Track, len: usize) -> bool {
+    len > 0
+        || (args_streaming()
+            && !local::is_local(&track.url)
+            && !cache::contains(&track.url)
+            && !prefetch::in_flight(&track.url))
+}
 
-        match Mp3::load(buf)?.symphonia_decoder().await {
-            Ok(mp3) => sink.append(mp3),
-            Err(e) => println!("skip: Decode Error {:?}", e),
-        }
-    };
+/// `track` becomes the current track on screen
+fn start(state: &SharedState, track: &Track) -> Result<()> {
+    let previous = state.get_current_track_info();
+    if !previous.url.is_empty() {
+        hooks::run(Hook::TrackEnd, &previous.to_track(), None);
+    }
+    state.record_history();
+    state.move_to_current_track(track);
+    update_song_info_on_screen(&state.get_current_track_info())?;
+    hooks::run(Hook::TrackStart, track, None);
     Ok(())
 }
 
+/// decoder over the downloaded buffer, or over the stream while it downloads
+async fn source(state: &SharedState, track: &Track, buf: Vec<u8>) -> Result<Option<BoxSource>> {
+    if !buf.is_empty() {
+        let mp3 = Mp3::load(buf)?;
+        let decoder = if local::is_local(&track.url) {
+            mp3.decoder().await
+        } else {
+            mp3.symphonia_decoder().await
+        };
+        return Ok(match decoder {
+            Ok(mp3) => Some(Box::new(mp3)),
+            Err(e) => {
+                hooks::run(Hook::Error, track, Some(&format!("Decode Error {:?}", e)));
+                println(&format!("skip: Decode Error {:?}", e));
+                state.remove_track(&track.url);
+                None
+            }
+        });
+    }
+
+    enable_spinner();
+    let source = stream::open(track).await.and_then(stream::decoder);
+    disable_spinner();
+    match source {
+        Ok(source) => Ok(Some(Box::new(source))),
+        Err(e) => {
+            hooks::run(Hook::Error, track, Some(&e.to_string()));
+            if !state.drop_failed(&track.url, &e) {
+                quit(e);
+            }
+            Ok(None)
+        }
+    }
+}
+
 async fn search(state: &SharedState) -> Result<()> {
     disable_tick_on_screen();
-    let search_str = state.show_input_panel()?;
+    let search_str = state.show_input_panel("? free word search")?;
     enable_tick_on_screen();
 
     if search_str.is_some() {
-        state.search(search_str).await?;
+        state.search(search_str, true).await?;
+    }
+    park_unlock();
+    Ok(())
+}
+
+async fn import(state: &SharedState) -> Result<()> {
+    disable_tick_on_screen();
+    let path = state.show_input_panel("? playlist file (m3u/text/json)")?;
+    enable_tick_on_screen();
+    park_unlock();
+
+    if let Some(path) = path {
+        if let Err(e) = state.import(Path::new(path.trim())).await {
+            println(&format!("import: {}", e));
+        }
+    }
+    Ok(())
+}
+
+/// move within the current track by `delta` seconds
+fn seek_by(state: &SharedState, deck: &Deck, delta: i64) {
+    let secs = (get_progress_bar_position() as i64 + delta).max(0);
+    seek(state, deck, secs as u64);
+}
+
+fn seek(state: &SharedState, deck: &Deck, secs: u64) {
+    if deck.empty() {
+        return;
+    }
+    // stay short of the end, the next track is queued from there
+    let duration = state.get_current_track_info().duration;
+    let secs = match duration > 0.0 {
+        true => secs.min((duration as u64).saturating_sub(1)),
+        false => secs,
+    };
+    match deck.seek(Duration::from_secs(secs)) {
+        Ok(()) => set_progress_bar_position(secs),
+        Err(e) => println(&format!("seek: {}", e)),
+    }
+}
+
+/// restart the current track, or go back to the previous one in its first seconds
+fn previous(state: &SharedState, deck: &mut Deck) {
+    if deck.empty() {
+        return;
+    }
+    if get_progress_bar_position() > RESTART_AFTER {
+        if deck.seek(Duration::ZERO).is_ok() {
+            set_progress_bar_position(0);
+            return;
+        }
+        // a stream can't seek, play it again
+        state.rewind(false);
+    } else if !state.rewind(true) {
+        println("previous: nothing to go back to");
+        return;
     }
+    state.set_skipped();
+    deck.stop();
+}
+
+/// jump to a percentage of the current track
+fn jump(state: &SharedState, deck: &Deck) -> Result<()> {
+    disable_tick_on_screen();
+    let percent = state.show_input_panel("? jump to % of the track")?;
+    enable_tick_on_screen();
     park_unlock();
+
+    let Some(percent) = percent.and_then(|x| x.trim().trim_end_matches('%').parse::<f32>().ok()) else {
+        return Ok(());
+    };
+    let duration = state.get_current_track_info().duration;
+    if duration <= 0.0 {
+        println("seek: the track length is unknown");
+        return Ok(());
+    }
+    seek(state, deck, (duration * percent.clamp(0.0, 100.0) / 100.0) as u64);
     Ok(())
 }
 
 fn help() -> Result<()> {
     let _dest = Dest();
     disable_tick_on_screen();
-    show_alt_term(&about().split('\n')
-        .map(|x|x.to_string()).collect::<Vec<_>>(), None)?;
+    let mut v = vec![
+        "A command line music player for https://bandcamp.com".to_string(),
+        "".to_string(),
+        format!("{:<20} {}", "[Key]", "[Description]"),
+    ];
+    v.extend(keymap().help());
+    v.push(format!(" {:<20} {}", "Ctrl+C", "exit"));
+    show_alt_term(&v, None)?;
 
     Ok(())
 }
@@ -271,18 +614,36 @@ fn info(state: &SharedState) -> Result<()> {
 
     let v = state.track_info()?;
 
-    match state.get_current_art_id() {
-        Some(art_id) => {
-            let url = format!("https://f4.bcbits.com/img/a{}_16.jpg", art_id);
-            let img = get_blocking_request(&url)?;
-            show_alt_term(&v, Option::from(img))?;
-        }
-        None => show_alt_term(&v, None)?,
-    }
+    // no art when offline
+    let url = state.get_current_track_info().url;
+    let img = if local::is_local(&url) {
+        local::embedded_art(&url)
+    } else {
+        state.get_current_art_id().and_then(|art_id| api().art(art_id).ok())
+    };
+    show_alt_term(&v, img)?;
 
     Ok(())
 }
 
+fn history(state: &SharedState) -> Result<()> {
+    let _dest = Dest();
+    disable_tick_on_screen();
+    state.show_history()
+}
+
+fn favorites(state: &SharedState) -> Result<()> {
+    let _dest = Dest();
+    disable_tick_on_screen();
+    state.show_favorites()
+}
+
+fn export(state: &SharedState) -> Result<()> {
+    let _dest = Dest();
+    disable_tick_on_screen();
+    state.export()
+}
+
 fn menu(state: &SharedState) -> Result<()> {
     let _dest = Dest();
     disable_tick_on_screen();
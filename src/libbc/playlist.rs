@@ -3,7 +3,7 @@ use std::collections::VecDeque;
 use std::io;
 
 use anyhow::{Error, Result};
-use bytes::BytesMut;
+use chrono::Local;
 use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
 use crossterm::terminal::{enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
 use crossterm::{cursor, execute};
@@ -15,32 +15,59 @@ use ratatui::backend::CrosstermBackend;
 use ratatui::widgets::Borders;
 use ratatui::Terminal;
 use regex::Regex;
-use scraper::Html;
 use tui_textarea::TextArea;
 
-use crate::libbc::http_client::{get_blocking_request, post_request};
+use crate::libbc::bandcamp_api::api;
+use crate::libbc::album;
+use crate::libbc::args::{args_album_mode, args_filter};
+use crate::libbc::cache::Cache;
+use crate::libbc::favorites::{load_favorites, Favorites};
+use crate::libbc::filter::accept;
+use crate::libbc::local;
 use crate::libbc::player::{park_lock, park_unlock};
-use crate::libbc::search::parse_doc;
 use crate::libbc::shared_data::SharedState;
 use crate::libbc::terminal;
 use crate::libbc::terminal::quit;
 use crate::models::bc_discover_index::{DiscoverIndexRequest, Element, PostData};
-use crate::models::bc_discover_json::{DiscoverJsonRequest, Results};
-use crate::models::bc_discover_tags::{DiscoverTagsJson, Struct, TagsPostData};
+use crate::models::bc_discover_json::Results;
+use crate::models::bc_discover_tags::{Struct, TagsPostData};
 use crate::models::bc_error::BcradioError;
-use crate::models::shared_data_models::{ResultsJson, Track};
+use crate::models::shared_data_models::{ResultsJson, Station, Track};
 use crate::{ceil, format_duration, lazy_regex};
-use crate::libbc::progress_bar::destroy;
+use crate::libbc::progress_bar::{destroy, println};
+
+const FAVORITES: &str = "★ favorites";
+const ANYWHERE: &str = "anywhere";
+const ANY_FORMAT: &str = "any format";
+/// discover pages fetched in a row when the filters match nothing
+const MAX_FILTERED_PAGES: usize = 10;
 
 pub trait PlayList {
     fn ask(&self) -> Result<PostData>;
-    fn silent(&self, genre: Option<String>, sub_genre: Option<String>) -> Result<PostData>;
-    async fn store_results(&self, post_data: &PostData);
+    fn silent(
+        &self,
+        genre: Option<String>,
+        sub_genre: Option<String>,
+        location: Option<String>,
+        slice: Option<String>,
+        category: Option<String>,
+    ) -> Result<PostData>;
+    async fn store_results(&self, post_data: &PostData) -> Result<()>;
     async fn fill_playlist(&self) -> Result<()>;
-    fn discover_index(&self, url: &str) -> Result<DiscoverIndexRequest>;
+    fn discover_index(&self) -> Result<DiscoverIndexRequest>;
     async fn discover_json(&self, post_data: &PostData) -> Result<Vec<Results>>;
+    async fn discover_tracks(&self, post_data: &PostData) -> Result<VecDeque<Track>>;
     async fn discover_tags_json(&self, post_data: &TagsPostData) -> Result<Vec<Element>>;
     fn choice(&self) -> Result<PostData>;
+    fn choice_genre(&self) -> Result<PostData>;
+    fn choice_location(&self) -> Result<Element>;
+    fn find_location(&self, name: &str) -> Result<Element>;
+    fn choice_slice(&self) -> Result<String>;
+    fn choice_category(&self) -> Result<Element>;
+    fn find_category(&self, name: &str) -> Result<Element>;
+    fn find_slice(&self, name: &str) -> Result<Element>;
+    async fn next_slice(&self) -> Result<()>;
+    fn load_filters(&self) -> Result<()>;
     fn gen_track_list(&self, items: &[Results]) -> Result<VecDeque<Track>>;
     fn top_menu(&self) -> Result<()>;
 }
@@ -53,7 +80,14 @@ impl PlayList for SharedState {
         Ok(post_data)
     }
 
-    fn silent(&self, genre: Option<String>, sub_genre: Option<String>) -> Result<PostData> {
+    fn silent(
+        &self,
+        genre: Option<String>,
+        sub_genre: Option<String>,
+        location: Option<String>,
+        slice: Option<String>,
+        category: Option<String>,
+    ) -> Result<PostData> {
         let v = [genre, sub_genre]
             .into_iter()
             .filter(|i| i.is_some())
@@ -61,28 +95,68 @@ impl PlayList for SharedState {
             .filter(|i| !i.is_empty())
             .collect::<Vec<_>>();
 
-        let post_data = PostData {
+        let location = match location {
+            Some(name) => self.find_location(&name)?,
+            None => Element::default(),
+        };
+        let category = match category {
+            Some(name) => self.find_category(&name)?,
+            None => Element::default(),
+        };
+        let mut post_data = PostData {
             tag_norm_names: v,
+            geoname_id: location.id,
+            category_id: category.id,
             ..Default::default()
         };
+        if let Some(name) = slice {
+            post_data.slice = self.find_slice(&name)?.slug;
+        }
+        self.set_location(location);
+        self.set_category(category);
 
         Ok(post_data)
     }
 
-    async fn store_results(&self, post_data: &PostData) {
-        let res = self.discover_json(post_data).await.unwrap();
-        let aa = self.gen_track_list(&res).unwrap();
+    async fn store_results(&self, post_data: &PostData) -> Result<()> {
+        match self.get_station() {
+            Station::Favorites => return self.append_favorites(),
+            Station::Local(path) => {
+                self.append_tracklist(local::scan(&path).unwrap_or_default());
+                return Ok(());
+            }
+            Station::Discover | Station::Playlist => {}
+        }
+        let aa = self.discover_tracks(post_data).await?;
         self.append_tracklist(aa);
+        Ok(())
     }
 
     async fn fill_playlist(&self) -> Result<()> {
         let l = self.queue_length_from_truck_list();
         if l < 2 {
+            match self.get_station() {
+                Station::Favorites if !load_favorites()?.is_empty() => {
+                    return self.append_favorites();
+                }
+                Station::Local(path) => {
+                    self.append_tracklist(local::scan(&path)?);
+                    return Ok(());
+                }
+                Station::Playlist if l > 0 => return Ok(()),
+                _ => {}
+            }
             match self.next_post().cursor {
                 Some(_) => {
                     let post_data = &self.next_post();
-                    let res = self.discover_json(post_data).await?;
-                    self.append_tracklist(self.gen_track_list(&res)?);
+                    match self.discover_tracks(post_data).await {
+                        Ok(res) => self.append_tracklist(res),
+                        Err(e) => {
+                            if !self.fallback_to_cache() {
+                                return Err(e);
+                            }
+                        }
+                    }
                 }
                 None => {
                     destroy();
@@ -90,7 +164,7 @@ impl PlayList for SharedState {
                     println!("playlist is empty.\r");
 
                     match self.ask() {
-                        Ok(post_data) => self.store_results(&post_data).await,
+                        Ok(post_data) => self.store_results(&post_data).await?,
                         _ => quit(Error::from(BcradioError::Quit)),
                     }
                 }
@@ -99,32 +173,12 @@ impl PlayList for SharedState {
         Ok(())
     }
 
-    fn discover_index(&self, url: &str) -> Result<DiscoverIndexRequest> {
-        let buf = get_blocking_request(url)?;
-
-        let slice = String::from_utf8(buf)?;
-        let doc = Html::parse_document(&slice);
-
-        let c = parse_doc(doc, "div[id='DiscoverApp']", "data-blob")?;
-
-        let json: Result<DiscoverIndexRequest, serde_json::Error> =
-            serde_json::from_slice(&bytes_mut(c.as_bytes())?);
-
-        match json {
-            Ok(r) => Ok(r),
-            Err(e) => {
-                eprintln!("{e}");
-                Err(Error::from(e))
-            },
-        }
+    fn discover_index(&self) -> Result<DiscoverIndexRequest> {
+        api().discover_index()
     }
 
     async fn discover_json(&self, post_data: &PostData) -> Result<Vec<Results>> {
-        let url = "https://bandcamp.com/api/discover/1/discover_web";
-        let a = post_request(url, post_data).await;
-
-        let json: DiscoverJsonRequest =
-            serde_json::from_slice(&bytes_mut(a?.as_slice())?)?;
+        let json = api().discover_web(post_data).await?;
 
         let aa = json.results;
         self.set_next_postdata(&PostData {
@@ -134,15 +188,27 @@ impl PlayList for SharedState {
         Ok(aa)
     }
 
-    async fn discover_tags_json(&self, post_data: &TagsPostData) -> Result<Vec<Element>> {
-        let url = "https://bandcamp.com/api/tag_search/2/related_tags";
-
-        let a = post_request(url, post_data).await;
+    /// keeps paging while the filters skip every item of a page
+    async fn discover_tracks(&self, post_data: &PostData) -> Result<VecDeque<Track>> {
+        let mut post_data = post_data.clone();
+        for _ in 0..MAX_FILTERED_PAGES {
+            let tracks = self.gen_track_list(&self.discover_json(&post_data).await?)?;
+            post_data = self.next_post();
+            if !tracks.is_empty() || post_data.cursor.is_none() {
+                if args_album_mode() {
+                    return Ok(album::expand(tracks).await);
+                }
+                return Ok(tracks);
+            }
+        }
+        println("filter: no matching items, adjust [filter]");
+        Ok(VecDeque::new())
+    }
 
-        let json: Result<DiscoverTagsJson, serde_json::Error> =
-            serde_json::from_slice(&bytes_mut(a?.as_slice())?);
+    async fn discover_tags_json(&self, post_data: &TagsPostData) -> Result<Vec<Element>> {
+        let json = api().related_tags(post_data).await;
         let s = match json
-            .unwrap_or_else(|_| DiscoverTagsJson::default())
+            .unwrap_or_default()
             .single_results
             .first()
         {
@@ -163,12 +229,29 @@ impl PlayList for SharedState {
     }
 
     fn choice(&self) -> Result<PostData> {
+        let post_data = self.choice_genre()?;
+        if self.get_station() != Station::Discover {
+            return Ok(post_data);
+        }
+        let location = self.choice_location()?;
+        let category = self.choice_category()?;
+        let post_data = PostData {
+            geoname_id: location.id,
+            category_id: category.id,
+            slice: self.choice_slice()?,
+            ..post_data
+        };
+        self.set_location(location);
+        self.set_category(category);
+        Ok(post_data)
+    }
+
+    fn choice_genre(&self) -> Result<PostData> {
         park_lock();
         inquire::set_global_render_config(render_config());
-        let url = "https://bandcamp.com/discover/";
 
         loop {
-            let r = self.discover_index(url);
+            let r = self.discover_index();
             let (g, t) = match r {
                 Ok(mut t) => {
                     self.set_subgenre("");
@@ -177,6 +260,9 @@ impl PlayList for SharedState {
                         ..Default::default()
                     }];
                     g.append(&mut t.app_data.initial_state.genres);
+                    self.save_locations(t.app_data.initial_state.locations);
+                    self.save_slices(t.app_data.initial_state.slices);
+                    self.save_categories(t.app_data.initial_state.categories);
                     let t = t.app_data.initial_state.subgenres;
                     self.save_genres(g.clone(), t.clone());
 
@@ -185,12 +271,26 @@ impl PlayList for SharedState {
                 Err(_) => self.get_genres(),
             };
 
-            let _genre_ans = Select::new("genre?", g.iter().map(|x| x.label.clone()).collect())
+            let mut options = g.iter().map(|x| x.label.clone()).collect::<Vec<_>>();
+            if !load_favorites().unwrap_or_default().is_empty() {
+                options.insert(1, FAVORITES.to_string());
+            }
+            let _genre_ans = Select::new("genre?", options)
                 .with_raw_return(true)
                 .prompt();
 
             let genre_ans = match _genre_ans {
+                Ok(ref choice) if choice == FAVORITES => {
+                    self.set_station(Station::Favorites);
+                    self.set_genre("");
+                    self.set_subgenre("");
+                    return Ok(PostData {
+                        cursor: None,
+                        ..Default::default()
+                    });
+                }
                 Ok(ref choice) => {
+                    self.set_station(Station::Discover);
                     self.set_genre(choice);
                     choice
                 },
@@ -354,9 +454,181 @@ impl PlayList for SharedState {
         }
     }
 
+    fn choice_location(&self) -> Result<Element> {
+        let mut locations = vec![Element {
+            label: ANYWHERE.to_string(),
+            ..Default::default()
+        }];
+        locations.extend(self.get_locations().into_iter().filter(|x| x.id != 0));
+        if locations.len() == 1 {
+            return Ok(Element::default());
+        }
+
+        match Select::new(
+            "location?",
+            locations.iter().map(|x| x.label.clone()).collect(),
+        )
+        .with_raw_return(false)
+        .prompt()
+        {
+            Ok(ref choice) => Ok(pick_element(&locations, choice)
+                .filter(|x| x.id != 0)
+                .unwrap_or_default()),
+            Err(InquireError::OperationInterrupted) => {
+                Err(Error::from(BcradioError::OperationInterrupted))
+            }
+            // esc: anywhere
+            Err(_) => Ok(Element::default()),
+        }
+    }
+
+    /// location label or slug, or a raw geoname id
+    fn find_location(&self, name: &str) -> Result<Element> {
+        if let Ok(id) = name.parse::<i64>() {
+            return Ok(Element {
+                id,
+                label: name.to_string(),
+                ..Default::default()
+            });
+        }
+        if self.get_locations().is_empty() {
+            self.load_filters()?;
+        }
+        find_element(&self.get_locations(), name)
+            .ok_or(Error::msg(format!("unknown location: {}", name)))
+    }
+
+    fn choice_category(&self) -> Result<Element> {
+        let mut categories = vec![Element {
+            label: ANY_FORMAT.to_string(),
+            ..Default::default()
+        }];
+        categories.extend(self.get_categories().into_iter().filter(|x| x.id != 0));
+        if categories.len() == 1 {
+            return Ok(Element::default());
+        }
+
+        match Select::new(
+            "format?",
+            categories.iter().map(|x| x.label.clone()).collect(),
+        )
+        .with_raw_return(false)
+        .prompt()
+        {
+            Ok(ref choice) => Ok(pick_element(&categories, choice)
+                .filter(|x| x.id != 0)
+                .unwrap_or_default()),
+            Err(InquireError::OperationInterrupted) => {
+                Err(Error::from(BcradioError::OperationInterrupted))
+            }
+            // esc: any format
+            Err(_) => Ok(Element::default()),
+        }
+    }
+
+    /// category label or slug
+    fn find_category(&self, name: &str) -> Result<Element> {
+        if self.get_categories().is_empty() {
+            self.load_filters()?;
+        }
+        find_element(&self.get_categories(), name).ok_or(Error::msg(format!(
+            "unknown category: {} ({})",
+            name,
+            self.get_categories().iter().map(|x| &x.slug).join(", ")
+        )))
+    }
+
+    /// "rand" when cancelled
+    fn choice_slice(&self) -> Result<String> {
+        let slices = self.get_slices();
+        if slices.is_empty() {
+            return Ok(PostData::default().slice);
+        }
+
+        let cursor = slices
+            .iter()
+            .position(|x| x.slug == PostData::default().slice)
+            .unwrap_or_default();
+        match Select::new("sort?", slices.iter().map(|x| x.label.clone()).collect())
+            .with_starting_cursor(cursor)
+            .with_raw_return(false)
+            .prompt()
+        {
+            Ok(ref choice) => Ok(pick_element(&slices, choice)
+                .map(|x| x.slug)
+                .unwrap_or(PostData::default().slice)),
+            Err(InquireError::OperationInterrupted) => {
+                Err(Error::from(BcradioError::OperationInterrupted))
+            }
+            Err(_) => Ok(PostData::default().slice),
+        }
+    }
+
+    /// slice label or slug
+    fn find_slice(&self, name: &str) -> Result<Element> {
+        if self.get_slices().is_empty() {
+            self.load_filters()?;
+        }
+        find_element(&self.get_slices(), name).ok_or(Error::msg(format!(
+            "unknown slice: {} ({})",
+            name,
+            self.get_slices().iter().map(|x| &x.slug).join(", ")
+        )))
+    }
+
+    /// re-query the discover station with the next slice and a fresh cursor
+    async fn next_slice(&self) -> Result<()> {
+        if self.get_station() != Station::Discover {
+            println("sort: discover stations only");
+            return Ok(());
+        }
+        if self.get_slices().is_empty() {
+            if let Err(e) = self.load_filters() {
+                println(&format!("sort: {}", e));
+                return Ok(());
+            }
+        }
+        let slices = self.get_slices();
+        let post_data = self.next_post();
+        let next = match slices.iter().position(|x| x.slug == post_data.slice) {
+            Some(i) => slices[(i + 1) % slices.len()].clone(),
+            None => match slices.first() {
+                Some(a) => a.clone(),
+                None => return Ok(()),
+            },
+        };
+
+        println(&format!("sort: {}", next.label));
+        let post_data = PostData {
+            slice: next.slug,
+            cursor: PostData::default().cursor,
+            ..post_data
+        };
+        // the queue stays as it is when the new slice can't be loaded
+        match self.discover_tracks(&post_data).await {
+            Ok(tracks) => {
+                self.clear_all_tracklist();
+                self.append_tracklist(tracks);
+            }
+            Err(e) => println(&format!("sort: {}", e)),
+        }
+        Ok(())
+    }
+
+    /// locations, slices and categories from the discover page
+    fn load_filters(&self) -> Result<()> {
+        let state = self.discover_index()?.app_data.initial_state;
+        self.save_locations(state.locations);
+        self.save_slices(state.slices);
+        self.save_categories(state.categories);
+        Ok(())
+    }
+
     fn gen_track_list(&self, items: &[Results]) -> Result<VecDeque<Track>> {
+        let filter = args_filter();
+        let today = Local::now().date_naive();
         let mut track_list = VecDeque::new();
-        for i in items.iter() {
+        for i in items.iter().filter(|x| accept(x, &filter, today)) {
             track_list.append(&mut VecDeque::from([Track {
                 album_title: i.title.to_owned(),
                 artist_name: i.featured_track.band_name.to_owned(),
@@ -398,7 +670,9 @@ impl PlayList for SharedState {
         match self.ask() {
             Ok(post_data) => {
                 self.clear_all_tracklist();
-                block_on(self.store_results(&post_data));
+                if let Err(e) = block_on(self.store_results(&post_data)) {
+                    println(&format!("menu: {}", e));
+                }
             }
             Err(e) => match e.downcast_ref().unwrap() {
                 BcradioError::InvalidUrl => {}
@@ -462,6 +736,13 @@ fn render_config() -> RenderConfig<'static> {
     }
 }
 
+/// case-insensitive label, or slug
+fn find_element(v: &[Element], name: &str) -> Option<Element> {
+    v.iter()
+        .find(|x| x.label.eq_ignore_ascii_case(name) || x.slug == slug(&name.to_lowercase()))
+        .cloned()
+}
+
 fn pick_element(g: &[Element], key: &str) -> Option<Element> {
     match g.iter().find(|&x| x.label == key) {
         None => g.iter().find(|&x| x.slug == key).cloned(),
@@ -469,12 +750,6 @@ fn pick_element(g: &[Element], key: &str) -> Option<Element> {
     }
 }
 
-fn bytes_mut(a: &[u8]) -> Result<BytesMut> {
-    let mut b = BytesMut::new();
-    b.extend_from_slice(a);
-    Ok(b)
-}
-
 lazy_regex!(
     RE1: r"[?#].*",
     RE2: r"[\[\]@!$'\(\)\*\+,:;=]",
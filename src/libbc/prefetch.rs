@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::io;
+use std::ops::Deref;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use futures::future::{abortable, AbortHandle};
+use log::info;
+
+use crate::libbc::album::Album;
+use crate::libbc::args::{args_prefetch, args_prefetch_memory};
+use crate::libbc::bandcamp_api::api;
+use crate::libbc::cache::{self, Cache};
+use crate::libbc::command::Command;
+use crate::libbc::hooks::{self, Hook};
+use crate::libbc::local;
+use crate::libbc::player::RXTX;
+use crate::libbc::progress_bar::{disable_spinner, enable_spinner};
+use crate::libbc::shared_data::SharedState;
+use crate::models::shared_data_models::Track;
+
+/// mp3-128 bytes per second, to size tracks that are not downloaded yet
+const BYTES_PER_SEC: f32 = 16_000.0;
+
+/// downloads in flight, by url
+static IN_FLIGHT: LazyLock<Mutex<HashMap<String, AbortHandle>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// a download of `url` is running
+pub fn in_flight(url: &str) -> bool {
+    IN_FLIGHT.lock().unwrap().contains_key(url)
+}
+
+pub trait Prefetch {
+    /// keep the next tracks of the queue downloaded, called on every player tick
+    fn prefetch(&self) -> Result<()>;
+}
+
+impl Prefetch for SharedState {
+    fn prefetch(&self) -> Result<()> {
+        let window = window(
+            self.peek_tracks(args_prefetch().max(1)),
+            args_prefetch_memory() * 1024 * 1024,
+        );
+        let wanted = window.iter().map(|x| x.0.url.to_owned()).collect::<Vec<_>>();
+
+        // the queue was edited: drop what fell out of the window
+        self.release_buffers(&wanted);
+        IN_FLIGHT.lock().unwrap().retain(|url, hdl| {
+            let keep = wanted.contains(url);
+            if !keep {
+                info!("prefetch abort {}\r", url);
+                hdl.abort();
+            }
+            keep
+        });
+
+        for (pos, (track, len)) in window.into_iter().enumerate() {
+            if len > 0 || in_flight(&track.url) {
+                continue;
+            }
+            let url = track.url.to_owned();
+            let (task, hdl) = abortable(download(self.clone(), track, pos == 0));
+            IN_FLIGHT.lock().unwrap().insert(url, hdl);
+            tokio::spawn(task);
+        }
+        Ok(())
+    }
+}
+
+/// upcoming tracks that fit in `budget` bytes, the head always does
+fn window(tracks: Vec<(Track, usize)>, budget: u64) -> Vec<(Track, usize)> {
+    let mut bytes = 0;
+    let mut v = Vec::new();
+    for (t, len) in tracks {
+        let size = match len {
+            0 => (t.duration * BYTES_PER_SEC) as u64,
+            n => n as u64,
+        };
+        if !v.is_empty() && bytes + size > budget {
+            break;
+        }
+        bytes += size;
+        v.push((t, len));
+    }
+    v
+}
+
+/// stops the spinner when the download ends or is aborted
+struct Spinner;
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        disable_spinner();
+    }
+}
+
+async fn download(state: SharedState, track: Track, head: bool) {
+    let _spinner = head.then(|| {
+        enable_spinner();
+        Spinner
+    });
+    let queued = track.url.to_owned();
+    let track = state.refresh(track).await;
+    if track.url != queued {
+        // the download goes on under the url the queue has now
+        let mut in_flight = IN_FLIGHT.lock().unwrap();
+        if let Some(hdl) = in_flight.remove(&queued) {
+            in_flight.insert(track.url.to_owned(), hdl);
+        }
+    }
+    let url = &track.url;
+    let buf = if local::is_local(url) {
+        local::read(url)
+    } else {
+        match cache::get(url) {
+            Some(buf) => Ok(buf),
+            None => api().stream(url).await.inspect(|buf| cache::put(&track, buf)),
+        }
+    };
+    match buf {
+        Ok(buf) => {
+            let duration = mp3_duration::from_read(&mut io::Cursor::new(buf.clone()))
+                .unwrap_or(Duration::from_secs_f32(track.duration));
+            state.set_track_buffer(url, buf, duration);
+        }
+        Err(e) => {
+            hooks::run(Hook::Error, &track, Some(&e.to_string()));
+            if !state.drop_failed(url, &e) {
+                // the player decides once the current track is over
+                let _ = RXTX.deref().0.send(Command::Failed(e.to_string())).await;
+            }
+        }
+    }
+    IN_FLIGHT.lock().unwrap().remove(url);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::window;
+    use crate::models::shared_data_models::Track;
+
+    fn track(url: &str, duration: f32, len: usize) -> (Track, usize) {
+        let t = Track {
+            url: url.to_string(),
+            duration,
+            ..Default::default()
+        };
+        (t, len)
+    }
+
+    #[test]
+    fn test_window() {
+        let q = vec![track("a", 600.0, 0), track("b", 10.0, 0), track("c", 10.0, 100)];
+        let urls = |v: Vec<(Track, usize)>| v.into_iter().map(|x| x.0.url).collect::<Vec<_>>();
+        assert_eq!(urls(window(q.clone(), 10_000_000)), ["a", "b", "c"]);
+        // the head is kept over budget
+        assert_eq!(urls(window(q.clone(), 1_000)), ["a"]);
+        assert_eq!(urls(window(q[1..].to_vec(), 160_100)), ["b", "c"]);
+        assert_eq!(urls(window(q[1..].to_vec(), 160_099)), ["b"]);
+    }
+}
@@ -14,10 +14,13 @@ use tokio::task::JoinHandle;
 
 use crate::ceil;
 use crate::format_duration;
+use crate::libbc::args::args_colors;
 use crate::libbc::player::PROG;
 use crate::models::shared_data_models::CurrentTrack;
 
 static PROGRESS_BAR: Mutex<Option<ProgressBar>> = Mutex::new(None);
+/// playback mode, kept across tracks
+static PREFIX: Mutex<String> = Mutex::new(String::new());
 
 #[allow(dead_code)]
 fn refresh_song_info_on_screen(local_time: DateTime<Local>, unixtime: u64) {
@@ -34,17 +37,18 @@ pub fn update_song_info_on_screen(item: &CurrentTrack) -> Result<()> {
 
     let dt = item.play_date;
     let dtf = dt.format("%H:%M:%S").to_string();
+    let c = args_colors();
 
-    println!("{}\r", dtf.rgb(90, 91, 103));
-    println!("{:<11} {}\r", "Song:".rgb(146, 49, 176), item.track);
+    println!("{}\r", dtf.rgb(c.time[0], c.time[1], c.time[2]));
+    println!("{:<11} {}\r", "Song:".rgb(c.song[0], c.song[1], c.song[2]), item.track);
     println!(
         "{:<11} {}\r",
-        "Artist:".rgb(126, 87, 194),
+        "Artist:".rgb(c.artist[0], c.artist[1], c.artist[2]),
         item.artist_name
     );
     println!(
         "{:<11} {}\r",
-        "Album:".rgb(121, 134, 203),
+        "Album:".rgb(c.album[0], c.album[1], c.album[2]),
         item.album_title
     );
 
@@ -68,7 +72,8 @@ pub fn update_song_info_on_screen(item: &CurrentTrack) -> Result<()> {
 
     let prog_bar = ProgressBar::new(progress_bar_len)
         .with_style(progress_bar_style)
-        .with_position(0);
+        .with_position(0)
+        .with_prefix(PREFIX.lock().unwrap().to_owned());
 
     PROGRESS_BAR.lock().unwrap().replace(prog_bar);
     Ok(())
@@ -93,6 +98,32 @@ pub fn get_progress_bar_current_position() -> Duration {
     PROGRESS_BAR.lock().unwrap().to_owned().unwrap().eta()
 }
 
+/// elapsed seconds of the current track, paused time excluded
+pub fn get_progress_bar_position() -> u64 {
+    match PROGRESS_BAR.lock().unwrap().as_ref() {
+        Some(p) => p.position(),
+        None => 0,
+    }
+}
+
+/// after a seek
+pub fn set_progress_bar_position(secs: u64) {
+    update_progress_bar(|p| p.set_position(secs));
+}
+
+pub fn set_progress_bar_prefix(prefix: &str) {
+    *PREFIX.lock().unwrap() = prefix.to_string();
+    update_progress_bar(|p| p.set_prefix(prefix.to_string()));
+}
+
+/// print a message above the progress bar
+pub fn println(msg: &str) {
+    match PROGRESS_BAR.lock().unwrap().as_ref() {
+        Some(p) => p.println(msg),
+        _ => println!("{}\r", msg),
+    }
+}
+
 pub fn enable_tick() {
     *PROG.lock().unwrap() = true;
 }
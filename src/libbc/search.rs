@@ -18,31 +18,29 @@ use std::io;
 use log::info;
 use tui_textarea::{Input, Key, TextArea};
 use crate::lazy_regex;
-use crate::libbc::http_adapter::{html_to_track, http_adapter};
-use crate::libbc::http_client::post_request;
+use crate::libbc::bandcamp_api::api;
 use crate::libbc::player::{park_lock, park_unlock};
 use crate::libbc::progress_bar::{disable_spinner, enable_spinner};
 use crate::libbc::scorer::score_sort;
 use crate::libbc::shared_data::SharedState;
 use crate::libbc::terminal::{clear_screen, draw};
 use crate::models::bc_error::BcradioError;
-use crate::models::search_models::{SearchJsonRequest, SearchJsonResponse};
+use crate::models::search_models::SearchJsonRequest;
 
 #[async_trait]
 pub trait Search {
-    async fn search(&self, search_text: Option<String>) -> Result<()>;
-    fn show_input_panel(&self) -> Result<Option<String>>;
+    /// with `ask`, the user picks among several artists, otherwise all results are queued
+    async fn search(&self, search_text: Option<String>, ask: bool) -> Result<()>;
+    fn show_input_panel(&self, title: &str) -> Result<Option<String>>;
 }
 
 #[async_trait]
 impl Search for SharedState {
-    async fn search(&self, mut search_text: Option<String>) -> Result<()> {
+    async fn search(&self, mut search_text: Option<String>, ask: bool) -> Result<()> {
         if search_text.is_none() {
             search_text = Option::from(self.get_current_track_info().artist_name);
         }
 
-        let url =
-            "https://bandcamp.com/api/bcsearch_public_api/1/autocomplete_elastic";
         let search_json_req = SearchJsonRequest {
             search_text: search_text.to_owned().unwrap(),
             search_filter: String::from("t"),
@@ -50,10 +48,7 @@ impl Search for SharedState {
             fan_id: None,
         };
 
-        let val = post_request(url, &search_json_req).await?;
-
-        let search_json_response =
-            simd_json::from_slice::<SearchJsonResponse>(val.clone().as_mut_slice())?;
+        let search_json_response = api().autocomplete(&search_json_req).await?;
         let mut v: Vec<String> = Vec::new();
         for search_item in search_json_response.auto.results {
             if let Some(url) = search_item.item_url_path.to_owned() {
@@ -67,13 +62,14 @@ impl Search for SharedState {
         use std::time::Instant; //debug
         let _start = Instant::now(); //debug
 
-        let mut r = http_adapter(url_list, html_to_track).await?;
+        let r = api().item_tracks(url_list).await;
         info!("Debug http_adapter: {:?}\r", _start.elapsed()); //debug
 
         disable_spinner();
+        let mut r = r?;
 
         let uniq = r.iter().unique_by(|p| &p.band_id).collect::<Vec<_>>();
-        if uniq.len() > 1 {
+        if ask && uniq.len() > 1 {
             park_lock();
 
             let stdout = io::stdout();
@@ -127,7 +123,7 @@ impl Search for SharedState {
         Ok(())
     }
 
-    fn show_input_panel(&self) -> Result<Option<String>> {
+    fn show_input_panel(&self, title: &str) -> Result<Option<String>> {
         let stdout = io::stdout();
         let mut stdout = stdout.lock();
 
@@ -140,7 +136,7 @@ impl Search for SharedState {
         textarea.set_block(
             ratatui::widgets::block::Block::default()
                 .borders(Borders::NONE)
-                .title("? free word search"),
+                .title(title.to_string()),
         );
 
         loop {
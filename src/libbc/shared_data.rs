@@ -1,27 +1,24 @@
-use anyhow::Result;
 use chrono::Local;
-use futures::future::{abortable, AbortHandle};
 use std::clone::Clone;
 use std::collections::VecDeque;
-use std::io;
 use std::iter::Iterator;
 use std::marker::PhantomData;
-use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use log::info;
-use crate::libbc::http_client::get_request;
-use crate::libbc::progress_bar::{disable_spinner, enable_spinner};
-use crate::libbc::terminal;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use crate::libbc::args::args_prefetch;
+use crate::libbc::listenbrainz;
 use crate::models::bc_discover_index::{Element, PostData};
-use crate::models::shared_data_models::{CurrentTrack, State, Track};
+use crate::models::shared_data_models::{CurrentTrack, PlayMode, Repeat, State, Station, Track};
 
-pub static ENQUE_FLG: AtomicBool = AtomicBool::new(true);
+/// played tracks kept for the previous key
+const BACK_STACK: usize = 100;
 
 #[derive(Default, Debug)]
 pub struct SharedState {
     pub state: Arc<Mutex<State>>,
-    pub task: Arc<Mutex<Vec<AbortHandle>>>,
     phantom: PhantomData<&'static ()>,
 }
 
@@ -29,7 +26,6 @@ impl Clone for SharedState {
     fn clone(&self) -> Self {
         SharedState {
             state: Arc::clone(&self.state),
-            task: self.task.clone(),
             phantom: Default::default(),
         }
     }
@@ -41,58 +37,45 @@ impl SharedState {
         lock.player.tracks.len()
     }
 
-    pub async fn enqueue_truck_buffer(&self) -> Result<()> {
-        let ss = self.clone();
-
-        let l: usize = ss.queue_length_from_truck_list();
-        if l > 0 && !ss.exists_track_buffer(0) && ENQUE_FLG.load(Ordering::Relaxed) {
-            // add audio buffer
-            let i = 0;
-            let a = tokio::spawn(async move {
-                if ENQUE_FLG
-                    .compare_exchange(true, false, Ordering::Acquire, Ordering::Relaxed)
-                    .unwrap()
-                {
-                    enable_spinner();
-                    let url = &ss.get_track_url(i);
-                    match get_request(url).await {
-                        Ok(buf) => {
-                            let duration =
-                            mp3_duration::from_read(&mut io::Cursor::new(buf.clone())).unwrap();
-                            ss.set_track_buffer(url, buf, duration);
-                        }
-                        Err(e) => {
-                            terminal::quit(e);
-                        }
-                    }
-
-                    disable_spinner();
-                };
-                let _ =
-                    ENQUE_FLG.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed);
-            });
-            let b = abortable(a).1;
-            self.append_task(b);
+    pub fn append_tracklist(&self, mut playlist: VecDeque<Track>) {
+        let mut lock = self.state.lock().unwrap();
+        if !lock.player.mode.shuffle {
+            lock.player.tracks.append(&mut playlist);
+            return;
+        }
+        // the new tracks mix with those queued behind the prefetched ones
+        let tracks = &mut lock.player.tracks;
+        let head = prefetched(tracks);
+        let mut rng = rand::thread_rng();
+        for t in playlist {
+            let pos = rng.gen_range(head..=tracks.len());
+            tracks.insert(pos, t);
         }
-        Ok(())
     }
 
-    fn append_task(&self, hdl: AbortHandle) {
-        let mut v = vec![hdl];
-        let mut lock = self.task.lock().unwrap();
-        lock.iter_mut().for_each(|i| i.abort());
-        lock.clear();
-        lock.append(&mut v);
+    pub fn push_front_tracklist(&self, playlist: Track) {
+        let mut lock = self.state.lock().unwrap();
+        lock.player.tracks.push_front(playlist);
     }
 
-    pub fn append_tracklist(&self, mut playlist: VecDeque<Track>) {
+    /// `url` was saved earlier, its stream may have expired
+    pub fn mark_stale(&self, url: &str) {
         let mut lock = self.state.lock().unwrap();
-        lock.player.tracks.append(&mut playlist);
+        lock.player.stale.insert(url.to_owned());
     }
 
-    pub fn push_front_tracklist(&self, playlist: Track) {
+    /// true once for a url marked stale
+    pub fn take_stale(&self, url: &str) -> bool {
         let mut lock = self.state.lock().unwrap();
-        lock.player.tracks.push_front(playlist);
+        lock.player.stale.remove(url)
+    }
+
+    /// the queued track at `url` streams from `new` now
+    pub fn set_track_url(&self, url: &str, new: &str) {
+        let mut lock = self.state.lock().unwrap();
+        if let Some(t) = lock.player.tracks.iter_mut().find(|x| x.url == url) {
+            t.url = new.to_owned();
+        }
     }
 
     #[allow(dead_code)]
@@ -101,6 +84,13 @@ impl SharedState {
         lock.player.tracks.insert(n, playlist);
     }
 
+    /// the prefetched head stays in place
+    pub fn shuffle_tracklist(&self) {
+        let mut lock = self.state.lock().unwrap();
+        let head = prefetched(&lock.player.tracks);
+        lock.player.tracks.make_contiguous()[head..].shuffle(&mut rand::thread_rng());
+    }
+
     pub fn clear_all_tracklist(&self) {
         info!("clear_all_tracklist\r");
         let mut lock = self.state.lock().unwrap();
@@ -136,6 +126,56 @@ impl SharedState {
         )
     }
 
+    pub fn save_locations(&self, locations: Vec<Element>) {
+        let mut lock = self.state.lock().unwrap();
+        lock.player.locations = locations;
+    }
+
+    pub fn get_locations(&self) -> Vec<Element> {
+        let lock = self.state.lock().unwrap();
+        lock.player.locations.to_owned()
+    }
+
+    pub fn save_slices(&self, slices: Vec<Element>) {
+        let mut lock = self.state.lock().unwrap();
+        lock.player.slices = slices;
+    }
+
+    pub fn get_slices(&self) -> Vec<Element> {
+        let lock = self.state.lock().unwrap();
+        lock.player.slices.to_owned()
+    }
+
+    pub fn save_categories(&self, categories: Vec<Element>) {
+        let mut lock = self.state.lock().unwrap();
+        lock.player.categories = categories;
+    }
+
+    pub fn get_categories(&self) -> Vec<Element> {
+        let lock = self.state.lock().unwrap();
+        lock.player.categories.to_owned()
+    }
+
+    pub fn set_category(&self, category: Element) {
+        let mut lock = self.state.lock().unwrap();
+        lock.player.category = category;
+    }
+
+    pub fn get_category(&self) -> Element {
+        let lock = self.state.lock().unwrap();
+        lock.player.category.to_owned()
+    }
+
+    pub fn set_location(&self, location: Element) {
+        let mut lock = self.state.lock().unwrap();
+        lock.player.location = location;
+    }
+
+    pub fn get_location(&self) -> Element {
+        let lock = self.state.lock().unwrap();
+        lock.player.location.to_owned()
+    }
+
     pub fn set_genre(&self, genre: &str) {
         let mut lock = self.state.lock().unwrap();
         lock.player.genre = genre.to_owned();
@@ -156,17 +196,18 @@ impl SharedState {
         lock.player.subgenre.to_owned()
     }
 
-    pub fn next_post(&self) -> PostData {
-        self.state.lock().unwrap().player.post_data.to_owned()
+    pub fn set_station(&self, station: Station) {
+        let mut lock = self.state.lock().unwrap();
+        lock.player.station = station;
     }
 
-    pub fn get_buffer_set_queue_length(&self) -> usize {
+    pub fn get_station(&self) -> Station {
         let lock = self.state.lock().unwrap();
-        lock.player
-            .tracks
-            .iter()
-            .filter(|&n| !n.buffer.is_empty())
-            .count()
+        lock.player.station.to_owned()
+    }
+
+    pub fn next_post(&self) -> PostData {
+        self.state.lock().unwrap().player.post_data.to_owned()
     }
 
     pub fn set_track_buffer(&self, url: &str, buf: Vec<u8>, duration: Duration) {
@@ -179,24 +220,56 @@ impl SharedState {
             }
         }
     }
-    pub fn exists_track_buffer(&self, pos: usize) -> bool {
-        let lock = self.state.lock().unwrap();
-        !lock.player.tracks[pos].buffer.is_empty()
-    }
-
     pub fn get_track_buffer(&self, pos: usize) -> Vec<u8> {
         let lock = self.state.lock().unwrap();
         lock.player.tracks[pos].buffer.to_owned()
     }
 
-    pub fn get_track_url(&self, pos: usize) -> String {
+    /// the first `n` tracks without their buffers, with the buffer size
+    pub fn peek_tracks(&self, n: usize) -> Vec<(Track, usize)> {
         let lock = self.state.lock().unwrap();
-        lock.player.tracks[pos].url.to_owned()
+        lock.player
+            .tracks
+            .iter()
+            .take(n)
+            .map(|x| (x.without_buffer(), x.buffer.len()))
+            .collect()
     }
 
-    pub fn move_to_current_track(&self) {
+    /// free the buffers of queued tracks that are not in `keep`
+    pub fn release_buffers(&self, keep: &[String]) {
         let mut lock = self.state.lock().unwrap();
-        let track = lock.player.tracks.pop_front().unwrap();
+        lock.player
+            .tracks
+            .iter_mut()
+            .filter(|x| !x.buffer.is_empty() && !keep.contains(&x.url))
+            .for_each(|x| x.buffer = vec![]);
+    }
+
+    pub fn remove_track(&self, url: &str) {
+        let mut lock = self.state.lock().unwrap();
+        lock.player.tracks.retain(|x| x.url != url);
+    }
+
+    /// `track` leaves the queue and becomes the current track
+    pub fn move_to_current_track(&self, track: &Track) {
+        let mut lock = self.state.lock().unwrap();
+        if let Some(pos) = lock.player.tracks.iter().position(|x| x.url == track.url) {
+            lock.player.tracks.remove(pos);
+        }
+        if !std::mem::take(&mut lock.player.rewound) && !lock.player.current_track.url.is_empty() {
+            let played = lock.player.current_track.to_track();
+            if lock.player.mode.repeat == Repeat::Queue {
+                lock.player.tracks.push_back(played.clone());
+            }
+            lock.player.back.push_back(played);
+            if lock.player.back.len() > BACK_STACK {
+                lock.player.back.pop_front();
+            }
+        }
+        let track = track.to_owned();
+        lock.player.skipped = false;
+        lock.player.current_track.url = track.url;
         lock.player.current_track.duration = track.duration;
         lock.player.current_track.track = track.track;
         lock.player.current_track.album_title = track.album_title;
@@ -207,6 +280,64 @@ impl SharedState {
         lock.player.current_track.results = track.results;
         lock.player.current_track.genre = track.genre;
         lock.player.current_track.subgenre = track.subgenre;
+        listenbrainz::playing_now(&lock.player.current_track);
+    }
+
+    /// move the queued track at `pos` to the front, false when there is none
+    pub fn promote_track(&self, pos: usize) -> bool {
+        let mut lock = self.state.lock().unwrap();
+        match lock.player.tracks.remove(pos) {
+            Some(t) => {
+                lock.player.tracks.push_front(t);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// queue the current track again, behind the previous one when `previous`.
+    /// false when there is nothing to go back to
+    pub fn rewind(&self, previous: bool) -> bool {
+        let mut lock = self.state.lock().unwrap();
+        if lock.player.current_track.url.is_empty() {
+            return false;
+        }
+        let back = match previous {
+            true => match lock.player.back.pop_back() {
+                Some(t) => Some(t),
+                None => return false,
+            },
+            false => None,
+        };
+        let current = lock.player.current_track.to_track();
+        lock.player.tracks.push_front(current);
+        if let Some(t) = back {
+            lock.player.tracks.push_front(t);
+        }
+        lock.player.rewound = true;
+        true
+    }
+
+    pub fn get_mode(&self) -> PlayMode {
+        let lock = self.state.lock().unwrap();
+        lock.player.mode
+    }
+
+    /// change the playback mode, returns the new one
+    pub fn update_mode<F: FnOnce(&mut PlayMode)>(&self, f: F) -> PlayMode {
+        let mut lock = self.state.lock().unwrap();
+        f(&mut lock.player.mode);
+        lock.player.mode
+    }
+
+    pub fn set_skipped(&self) {
+        let mut lock = self.state.lock().unwrap();
+        lock.player.skipped = true;
+    }
+
+    pub fn is_skipped(&self) -> bool {
+        let lock = self.state.lock().unwrap();
+        lock.player.skipped
     }
 
     pub fn get_current_track_info(&self) -> CurrentTrack {
@@ -219,3 +350,9 @@ impl SharedState {
         lock.player.current_track.art_id
     }
 }
+
+/// the head of the queue that is downloaded or being downloaded
+fn prefetched(tracks: &VecDeque<Track>) -> usize {
+    let buffered = tracks.iter().rposition(|x| !x.buffer.is_empty()).map_or(0, |i| i + 1);
+    buffered.max(args_prefetch().max(1)).min(tracks.len())
+}
@@ -87,4 +87,15 @@ impl Mp3 {
             Ok(decoder) => Ok(decoder),
         }
     }
+    /// any format symphonia can probe (local files)
+    pub async fn decoder(&self) -> Result<rodio::decoder::Decoder<MediaSourceStream>> {
+        let mss = MediaSourceStream::new(
+            Box::new(self.cursor()) as Box<dyn MediaSource>,
+            Default::default(),
+        );
+        match rodio::decoder::Decoder::new(mss) {
+            Err(e) => Err(Error::from(e)),
+            Ok(decoder) => Ok(decoder),
+        }
+    }
 }
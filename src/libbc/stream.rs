@@ -0,0 +1,287 @@
+use std::collections::VecDeque;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use futures::StreamExt;
+use log::info;
+use rodio::source::SeekError;
+use rodio::{Decoder, Source};
+use symphonia::core::io::MediaSource;
+
+use crate::libbc::args::args_stream_buffer;
+use crate::libbc::bandcamp_api::api;
+use crate::libbc::cache;
+use crate::libbc::progress_bar::println;
+use crate::models::shared_data_models::Track;
+
+/// give up on a stalled connection, the track ends
+const STALL_TIMEOUT: Duration = Duration::from_secs(15);
+/// producer backoff while the ring is full
+const FULL_WAIT: Duration = Duration::from_millis(10);
+/// bytes the ring must hold before the decoder is asked for more,
+/// a few mp3 frames so that a read never waits on the network
+const LOW_WATER: usize = 4096;
+
+/// only the producer and `Read::read` take the lock, the audio thread
+/// looks at the fill level and the flags
+#[derive(Default)]
+struct Ring {
+    buf: Mutex<VecDeque<u8>>,
+    cvar: Condvar,
+    cap: usize,
+    /// bytes in `buf`
+    fill: AtomicUsize,
+    /// bytes handed to the decoder
+    pos: AtomicU64,
+    done: AtomicBool,
+    starving: AtomicBool,
+    underruns: AtomicUsize,
+}
+
+impl Ring {
+    /// the decoder can read without waiting
+    fn ready(&self) -> bool {
+        self.done.load(Ordering::Acquire)
+            || self.fill.load(Ordering::Acquire) >= LOW_WATER.min(self.cap)
+    }
+
+    /// the first bytes are buffering, not an underrun
+    fn underrun(&self) {
+        if self.pos.load(Ordering::Relaxed) > 0 && !self.starving.swap(true, Ordering::Relaxed) {
+            let n = self.underruns.fetch_add(1, Ordering::Relaxed) + 1;
+            println(&format!("stream: underrun ({})", n));
+        }
+    }
+
+    /// append what fits, returns the bytes taken
+    fn push(&self, bytes: &[u8]) -> usize {
+        let mut buf = self.buf.lock().unwrap();
+        let n = bytes.len().min(self.cap.saturating_sub(buf.len()));
+        buf.extend(&bytes[..n]);
+        self.fill.store(buf.len(), Ordering::Release);
+        drop(buf);
+        if n > 0 {
+            self.cvar.notify_all();
+        }
+        n
+    }
+
+    fn finish(&self) {
+        // under the lock, a reader can't miss it between its check and its wait
+        let _buf = self.buf.lock().unwrap();
+        self.done.store(true, Ordering::Release);
+        self.cvar.notify_all();
+    }
+}
+
+/// mp3 body fed by the download task, read by the decoder while it arrives
+pub struct StreamSource {
+    ring: Arc<Ring>,
+}
+
+impl Read for StreamSource {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let ring = &*self.ring;
+        let mut buf = ring.buf.lock().unwrap();
+        loop {
+            if !buf.is_empty() {
+                let n = out.len().min(buf.len());
+                for (o, b) in out.iter_mut().zip(buf.drain(..n)) {
+                    *o = b;
+                }
+                ring.fill.store(buf.len(), Ordering::Release);
+                ring.pos.fetch_add(n as u64, Ordering::Relaxed);
+                ring.starving.store(false, Ordering::Relaxed);
+                return Ok(n);
+            }
+            if ring.done.load(Ordering::Acquire) {
+                return Ok(0);
+            }
+            ring.underrun();
+            let (b, timeout) = ring.cvar.wait_timeout(buf, STALL_TIMEOUT).unwrap();
+            buf = b;
+            if timeout.timed_out() && buf.is_empty() && !ring.done.load(Ordering::Acquire) {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "stream stalled"));
+            }
+        }
+    }
+}
+
+/// forward only, the position is all the decoder asks for
+impl Seek for StreamSource {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match pos {
+            SeekFrom::Current(0) => Ok(self.ring.pos.load(Ordering::Relaxed)),
+            _ => Err(io::Error::new(io::ErrorKind::Unsupported, "stream is not seekable")),
+        }
+    }
+}
+
+impl MediaSource for StreamSource {
+    fn is_seekable(&self) -> bool {
+        false
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// start downloading `track`, the decoder can be built as soon as this returns.
+/// the whole body goes to the disk cache once it is complete
+pub async fn open(track: &Track) -> Result<StreamSource> {
+    let mut chunks = api().stream_chunks(&track.url).await?;
+    let ring = Arc::new(Ring {
+        cap: args_stream_buffer() * 1024,
+        ..Default::default()
+    });
+
+    let producer = Arc::clone(&ring);
+    let track = track.to_owned();
+    // the whole body is only kept for the cache
+    let mut whole = cache::enabled().then(Vec::new);
+    tokio::spawn(async move {
+        let mut complete = true;
+        'recv: while let Some(chunk) = chunks.next().await {
+            let chunk = match chunk {
+                Ok(a) => a,
+                Err(e) => {
+                    info!("stream {} {}\r", track.url, e);
+                    complete = false;
+                    break;
+                }
+            };
+            let mut rest = &chunk[..];
+            while !rest.is_empty() {
+                // the player dropped the source
+                if Arc::strong_count(&producer) == 1 {
+                    complete = false;
+                    break 'recv;
+                }
+                let n = producer.push(rest);
+                if n == 0 {
+                    tokio::time::sleep(FULL_WAIT).await;
+                    continue;
+                }
+                rest = &rest[n..];
+            }
+            if let Some(whole) = whole.as_mut() {
+                whole.extend_from_slice(&chunk);
+            }
+        }
+        producer.finish();
+        if let Some(whole) = whole.filter(|_| complete) {
+            cache::put(&track, &whole);
+        }
+    });
+
+    Ok(StreamSource { ring })
+}
+
+/// blocks until the first frames have arrived
+pub fn decoder(source: StreamSource) -> Result<StreamDecoder> {
+    let ring = Arc::clone(&source.ring);
+    let input = tokio::task::block_in_place(|| Decoder::new_mp3(source))?;
+    Ok(StreamDecoder {
+        input,
+        ring,
+        frame_pos: 0,
+        stalled: None,
+    })
+}
+
+/// plays silence while the ring runs low instead of blocking the mixer in a read,
+/// the other deck keeps playing during a crossfade. ends after `STALL_TIMEOUT`
+pub struct StreamDecoder {
+    input: Decoder<StreamSource>,
+    ring: Arc<Ring>,
+    /// sample index within the current frame of all channels
+    frame_pos: u16,
+    stalled: Option<Instant>,
+}
+
+impl Iterator for StreamDecoder {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if self.frame_pos == 0 {
+            match self.ring.ready() {
+                true => self.stalled = None,
+                false => {
+                    self.ring.underrun();
+                    if self.stalled.get_or_insert_with(Instant::now).elapsed() > STALL_TIMEOUT {
+                        info!("stream stalled\r");
+                        return None;
+                    }
+                }
+            }
+        }
+        self.frame_pos = (self.frame_pos + 1) % self.input.channels().max(1);
+        match self.stalled {
+            Some(_) => Some(0),
+            None => self.input.next(),
+        }
+    }
+}
+
+impl Source for StreamDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        // silence shifts the decoder's frames, the format never changes
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use super::{Ring, StreamSource};
+
+    #[test]
+    fn test_read() {
+        let ring = Arc::new(Ring {
+            cap: 4,
+            ..Default::default()
+        });
+        let mut source = StreamSource {
+            ring: Arc::clone(&ring),
+        };
+
+        let producer = thread::spawn(move || {
+            for chunk in [b"ab", b"cd", b"ef"] {
+                thread::sleep(Duration::from_millis(20));
+                ring.push(chunk);
+            }
+            ring.finish();
+        });
+
+        let mut out = Vec::new();
+        source.read_to_end(&mut out).unwrap();
+        producer.join().unwrap();
+        assert_eq!(out, b"abcdef");
+        assert_eq!(source.ring.underruns.load(Ordering::Relaxed), 2);
+    }
+}
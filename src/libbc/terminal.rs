@@ -13,7 +13,7 @@ use std::fmt::Display;
 use std::io::StdoutLock;
 use std::{cmp, io, process};
 use anyhow::Error;
-use tui_textarea::{CursorMove, Input, Key, TextArea};
+use tui_textarea::{CursorMove, Input, Key, Scrolling, TextArea};
 use viu::app;
 use viu::config::Config;
 use viuer::Config as ViuerConfig;
@@ -186,7 +186,23 @@ pub fn draw_img(
     Ok(())
 }
 
+pub enum ListAction {
+    Select(usize),
+    Delete(usize),
+}
+
 pub fn show_alt_term2<T>(v: &Vec<T>) -> anyhow::Result<Option<usize>>
+where
+    T: Into<String>, String: for<'a> From<&'a T>
+{
+    Ok(match show_alt_term3(v, false)? {
+        Some(ListAction::Select(l)) => Some(l),
+        _ => None,
+    })
+}
+
+/// list view, `d` key deletes the line under the cursor when `deletable`
+pub fn show_alt_term3<T>(v: &Vec<T>, deletable: bool) -> anyhow::Result<Option<ListAction>>
 where
     T: Into<String>, String: for<'a> From<&'a T>
 {
@@ -227,6 +243,18 @@ where
                     textarea.move_cursor(CursorMove::Down);
                 }
             }
+            Input { key: Key::PageUp, .. } => textarea.scroll(Scrolling::PageUp),
+            Input { key: Key::PageDown, .. } => textarea.scroll(Scrolling::PageDown),
+            Input {
+                key: Key::Char('d'),
+                ..
+            } if deletable => {
+                line = match textarea.cursor().0 {
+                    0 => None,
+                    n => Some(ListAction::Delete(n)),
+                };
+                break;
+            }
             Input {
                 key: Key::Char(_c), // any
                 ..
@@ -236,7 +264,7 @@ where
             } => {
                 line = match textarea.cursor().0 {
                     0 => None,
-                    _ => Some(textarea.cursor().0),
+                    n => Some(ListAction::Select(n)),
                 };
                 break;
             }
@@ -6,7 +6,11 @@ use libbc::player::{PARK, RXTX};
 use std::ops::Deref;
 use std::time::Duration;
 
-use crate::libbc::args::init_args;
+use crate::libbc::args::{args_ctl, args_export, init_args};
+use crate::libbc::command::{keymap, Command, Key};
+#[cfg(unix)]
+use crate::libbc::control;
+use crate::libbc::export;
 use crate::libbc::player;
 use crate::libbc::player::park_lock;
 use crate::libbc::shared_data::SharedState;
@@ -26,8 +30,23 @@ const LOGO: &str = r#"
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let _exit = terminal::Quit;
     init_args();
+    if let Some((source, format, path)) = args_export() {
+        if let Err(e) = export::export_cli(source, format, path) {
+            terminal::print_error(e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    #[cfg(unix)]
+    if let Some((method, params)) = args_ctl() {
+        if let Err(e) = control::ctl(&method, &params) {
+            terminal::print_error(e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    let _exit = terminal::Quit;
     terminal::init();
 
     println!("{}", LOGO);
@@ -55,18 +74,14 @@ async fn start_playing() -> Result<()> {
                     }
                     Event::Key(e) => {
                         if e.kind == KeyEventKind::Press {
-                            if let KeyCode::Char(c) = e.code {
-                                match c {
-                                    's' | 'h' | 'm' | 'i' | 'l' => {
-                                        park_lock();
-                                        RXTX.deref().0.send(c).await?
-                                    }
-                                    'a'..='z' | '0'..='9' => RXTX.deref().0.send(c).await?,
-                                    'Q' => {
-                                        RXTX.deref().0.send(c).await?;
-                                        break;
-                                    }
-                                    _ => {}
+                            if let Some(cmd) = keymap().get(Key::from(&e)) {
+                                if cmd.parks() {
+                                    park_lock();
+                                }
+                                let quit = cmd == Command::Quit;
+                                RXTX.deref().0.send(cmd).await?;
+                                if quit {
+                                    break;
                                 }
                             }
                         }
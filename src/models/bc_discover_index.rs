@@ -56,9 +56,9 @@ pub struct SeoData {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PostData {
-    pub category_id: i16,
+    pub category_id: i64,
     pub tag_norm_names: Vec<String>,
-    pub geoname_id: i16,
+    pub geoname_id: i64,
     pub slice: String,
     pub cursor: Option<String>,
     pub size: i16,
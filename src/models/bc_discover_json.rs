@@ -91,7 +91,7 @@ pub struct Package {
     pub title: String,
     pub format: String,
     pub image_id: i64,
-    price: Price,
+    pub(crate) price: Price,
     pub type_id: i32,
 }
 
@@ -0,0 +1,27 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::models::shared_data_models::Track;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FavoriteRecord {
+    pub starred_at: DateTime<Local>,
+    pub item_url: Option<String>,
+    pub track: Track,
+}
+
+impl FavoriteRecord {
+    pub fn new(track: Track) -> Self {
+        FavoriteRecord {
+            starred_at: Local::now(),
+            item_url: track.results.item_url(),
+            track,
+        }
+    }
+
+    pub fn is(&self, t: &Track) -> bool {
+        self.track.artist_name == t.artist_name
+            && self.track.album_title == t.album_title
+            && self.track.track == t.track
+    }
+}
@@ -0,0 +1,80 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::models::search_models::{Current, ItemPage};
+use crate::models::shared_data_models::{CurrentTrack, ResultsJson, Track};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlayStatus {
+    Finished,
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryRecord {
+    pub played_at: DateTime<Local>,
+    pub status: PlayStatus,
+    /// seconds actually played
+    pub played: u64,
+    pub duration: f32,
+    pub artist_name: String,
+    pub album_title: String,
+    pub track: String,
+    pub url: String,
+    pub item_url: Option<String>,
+    pub genre: Option<String>,
+    pub subgenre: Option<String>,
+    pub art_id: Option<i64>,
+    pub band_id: i64,
+}
+
+impl HistoryRecord {
+    pub fn new(t: &CurrentTrack, status: PlayStatus, played: u64) -> Self {
+        HistoryRecord {
+            played_at: t.play_date,
+            status,
+            played,
+            duration: t.duration,
+            artist_name: t.artist_name.to_owned(),
+            album_title: t.album_title.to_owned(),
+            track: t.track.to_owned(),
+            url: t.url.to_owned(),
+            item_url: t.results.item_url(),
+            genre: t.genre.to_owned(),
+            subgenre: t.subgenre.to_owned(),
+            art_id: t.art_id,
+            band_id: t.band_id,
+        }
+    }
+
+    pub fn to_track(&self) -> Track {
+        Track {
+            album_title: self.album_title.to_owned(),
+            artist_name: self.artist_name.to_owned(),
+            art_id: self.art_id,
+            band_id: self.band_id,
+            url: self.url.to_owned(),
+            duration: self.duration,
+            track: self.track.to_owned(),
+            buffer: vec![],
+            results: match &self.item_url {
+                None => ResultsJson::None,
+                Some(item_url) => ResultsJson::Search(Box::new(ItemPage {
+                    current: Current {
+                        title: self.album_title.to_owned(),
+                        art_id: self.art_id,
+                        band_id: self.band_id,
+                        release_date: String::new(),
+                    },
+                    artist: self.artist_name.to_owned(),
+                    trackinfo: vec![],
+                    album_url: None,
+                    item_url: Some(item_url.to_owned()),
+                })),
+            },
+            genre: self.genre.to_owned(),
+            subgenre: self.subgenre.to_owned(),
+        }
+    }
+}
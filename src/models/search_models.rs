@@ -78,7 +78,7 @@ pub struct TrackInfo {
     track_num: Option<i32>,
     album_preorder: Option<bool>,
     unreleased_track: Option<bool>,
-    title_link: Option<String>,
+    pub title_link: Option<String>,
     has_lyrics: Option<bool>,
     has_info: Option<bool>,
     streaming: Option<i32>,
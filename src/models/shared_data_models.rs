@@ -1,5 +1,6 @@
-use std::collections::VecDeque;
+use std::collections::{HashSet, VecDeque};
 use std::marker::PhantomData;
+use std::path::PathBuf;
 
 use crate::models::bc_discover_index::{Element, PostData};
 use crate::models::bc_discover_json::Results;
@@ -16,6 +17,7 @@ pub struct Track {
     pub url: String,
     pub duration: f32,
     pub track: String,
+    #[serde(skip)]
     pub buffer: Vec<u8>,
     pub results: ResultsJson,
     pub genre: Option<String>,
@@ -40,6 +42,25 @@ impl Clone for Track {
     }
 }
 
+impl Track {
+    /// a copy without the audio bytes
+    pub fn without_buffer(&self) -> Track {
+        Track {
+            album_title: self.album_title.clone(),
+            artist_name: self.artist_name.clone(),
+            art_id: self.art_id,
+            band_id: self.band_id,
+            url: self.url.clone(),
+            duration: self.duration,
+            track: self.track.clone(),
+            buffer: vec![],
+            results: self.results.clone(),
+            genre: self.genre.clone(),
+            subgenre: self.subgenre.clone(),
+        }
+    }
+}
+
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub enum ResultsJson {
     Select(Box<Results>),
@@ -48,8 +69,31 @@ pub enum ResultsJson {
     None,
 }
 
+impl ResultsJson {
+    pub fn item_url(&self) -> Option<String> {
+        match self {
+            ResultsJson::Select(g) => Some(g.item_url.to_owned()),
+            ResultsJson::Search(g) => g.item_url.to_owned(),
+            ResultsJson::None => None,
+        }
+    }
+
+    /// album page of the item, the item itself for singles
+    pub fn release_url(&self) -> Option<String> {
+        match self {
+            ResultsJson::Search(g) => g
+                .album_url
+                .to_owned()
+                .filter(|x| !x.is_empty())
+                .or(g.item_url.to_owned()),
+            _ => self.item_url(),
+        }
+    }
+}
+
 #[derive(Default, Debug)]
 pub struct CurrentTrack {
+    pub url: String,
     pub duration: f32,
     pub track: String,
     pub art_id: Option<i64>,
@@ -66,6 +110,7 @@ pub struct CurrentTrack {
 impl Clone for CurrentTrack {
     fn clone(&self) -> CurrentTrack {
         CurrentTrack {
+            url: self.url.clone(),
             duration: self.duration,
             track: self.track.clone(),
             art_id: self.art_id,
@@ -81,6 +126,60 @@ impl Clone for CurrentTrack {
     }
 }
 
+impl CurrentTrack {
+    pub fn to_track(&self) -> Track {
+        Track {
+            album_title: self.album_title.clone(),
+            artist_name: self.artist_name.clone(),
+            art_id: self.art_id,
+            band_id: self.band_id,
+            url: self.url.clone(),
+            duration: self.duration,
+            track: self.track.clone(),
+            buffer: vec![],
+            results: self.results.clone(),
+            genre: self.genre.clone(),
+            subgenre: self.subgenre.clone(),
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq)]
+pub enum Station {
+    #[default]
+    Discover,
+    Favorites,
+    Local(PathBuf),
+    /// imported playlist, back to the menu once it has been played
+    Playlist,
+}
+
+impl Station {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Station::Discover => "discover",
+            Station::Favorites => "favorites",
+            Station::Local(_) => "local",
+            Station::Playlist => "playlist",
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub enum Repeat {
+    #[default]
+    Off,
+    One,
+    Queue,
+}
+
+#[derive(Default, Debug, Clone, Copy)]
+pub struct PlayMode {
+    pub shuffle: bool,
+    pub repeat: Repeat,
+    pub stop_after: bool,
+}
+
 #[derive(Default, Debug)]
 pub struct State {
     pub player: PlaylistInfo,
@@ -111,6 +210,23 @@ pub struct PlaylistInfo {
     pub post_data: PostData,
     pub genres: Vec<Element>,
     pub subgenres: Vec<Element>,
+    pub locations: Vec<Element>,
+    /// station location, id 0 is anywhere
+    pub location: Element,
+    pub slices: Vec<Element>,
+    pub categories: Vec<Element>,
+    /// station format, id 0 is any
+    pub category: Element,
     pub genre: String,
     pub subgenre: String,
+    pub skipped: bool,
+    pub station: Station,
+    /// played tracks without their buffers, the latest last
+    pub back: VecDeque<Track>,
+    /// the next track comes from the back stack, the current one is not pushed
+    pub rewound: bool,
+    pub mode: PlayMode,
+    /// stream urls saved in favorites or history, they expire and
+    /// are resolved again from the item page before they are fetched
+    pub stale: HashSet<String>,
 }